@@ -0,0 +1,111 @@
+//! Search benchmark: runs a fixed-depth search on a set of positions and
+//! prints nodes searched, time and nodes per second for each.
+//!
+//! ```text
+//! cargo run --release --example bench [depth]
+//! ```
+//!
+//! `cargo test` runs the harness too, at shallow depths, so it can't rot.
+
+use chinese_chess::ai::{self, SearchStats};
+use chinese_chess::game::{Board, Pos};
+use chinese_chess::notation;
+
+const DEFAULT_DEPTH: u32 = 3;
+
+/// Openings, middlegames and endgames, all with Red to move.
+const POSITIONS: [(&str, &str); 6] = [
+    (
+        "start",
+        "rnbakabnr/9/1c5c1/p1p1p1p1p/9/9/P1P1P1P1P/1C5C1/9/RNBAKABNR w - - 0 1",
+    ),
+    (
+        "central cannons",
+        "r1bakabr1/9/1cn3nc1/p3p1p1p/2p6/9/P1P1P1P1P/3CC1N2/9/RNBAKABR1 w - - 8 5",
+    ),
+    (
+        "middlegame",
+        "1rbakabr1/9/2n3n1c/p1p1C3p/9/2P3p2/Pc2P3P/2N3N1C/9/R1BAKABR1 w - - 1 9",
+    ),
+    (
+        "chariot and horse attack",
+        "3k5/4a4/4b4/9/9/9/9/4B4/4A4/2RHK4 w - - 0 1",
+    ),
+    (
+        "cannon against horse and cannon",
+        "2bak4/4a4/4b4/9/2n6/6C2/9/4B4/4AK3/3A1c3 w - - 0 1",
+    ),
+    (
+        "soldier endgame",
+        "3akab2/9/4b4/p3p3p/9/2P6/P3c3P/4B4/4A4/2BAK4 w - - 0 1",
+    ),
+];
+
+fn main() {
+    let depth = match std::env::args().nth(1) {
+        Some(arg) => arg.parse().expect("depth must be a number"),
+        None => DEFAULT_DEPTH,
+    };
+
+    let mut total_nodes = 0;
+    let mut total_time = 0.0;
+    println!("depth {}", depth);
+    for (name, best, stats) in run(depth) {
+        let seconds = stats.elapsed.as_secs_f64();
+        let best = best.map_or("none".to_string(), |(from, to)| {
+            notation::move_to_iccs(from, to)
+        });
+        println!(
+            "{:<32} {:>5} {:>10} nodes {:>8.3}s {:>10.0} nodes/s",
+            name,
+            best,
+            stats.nodes_searched,
+            seconds,
+            stats.nodes_searched as f64 / seconds
+        );
+        total_nodes += stats.nodes_searched;
+        total_time += seconds;
+    }
+    println!(
+        "{:<32} {:>5} {:>10} nodes {:>8.3}s {:>10.0} nodes/s",
+        "total",
+        "",
+        total_nodes,
+        total_time,
+        total_nodes as f64 / total_time
+    );
+}
+
+/// A position's name, best move and search statistics.
+type BenchResult = (&'static str, Option<(Pos, Pos)>, SearchStats);
+
+/// Searches every position to `depth`.
+fn run(depth: u32) -> Vec<BenchResult> {
+    POSITIONS
+        .iter()
+        .map(|&(name, fen)| {
+            let board = Board::from_fen(fen).expect("benchmark positions are valid");
+            let (best, stats) = ai::best_move_with_stats(&board, board.turn, depth);
+            (name, best, stats)
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn harness_runs_at_shallow_depths() {
+        for depth in [1, 2] {
+            let results = run(depth);
+            assert_eq!(results.len(), POSITIONS.len());
+            for ((name, best, stats), (_, fen)) in results.into_iter().zip(POSITIONS) {
+                let board = Board::from_fen(fen).unwrap();
+                let (from, to) = best.unwrap_or_else(|| panic!("{}: no move", name));
+                assert!(board.legal_moves_from(from).contains(&to), "{}", name);
+                assert!(stats.nodes_searched > 0, "{}", name);
+            }
+        }
+    }
+}
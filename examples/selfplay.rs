@@ -0,0 +1,160 @@
+//! Random self-play stress test: plays games of uniformly random legal
+//! moves and checks after every move that the position is still valid, the
+//! turn has passed, no piece has appeared and both Generals are still on
+//! the board. Any panic or broken check is reported with the seed that
+//! replays the game.
+//!
+//! ```text
+//! cargo run --release --example selfplay [games] [seed]
+//! ```
+//!
+//! Game `i` of a run with seed `s` is played with seed `s + i`, so
+//! `selfplay 1 <seed>` replays a single failing game. `cargo test` plays a
+//! few games too, from `SEED_ENV` or a fixed seed.
+
+use std::panic;
+use std::process;
+
+use chinese_chess::game::{Board, DEFAULT_INACTIVITY_LIMIT, GameState, HEIGHT, Pos, WIDTH};
+use chinese_chess::notation;
+
+const DEFAULT_GAMES: u64 = 1000;
+
+/// Random play rarely mates, so most games end by the inactivity rule.
+/// Every capture restarts its count and there are at most 30 captures,
+/// so no game can last longer than this.
+const MAX_PLIES: usize = 31 * DEFAULT_INACTIVITY_LIMIT as usize;
+
+fn main() {
+    let mut args = std::env::args().skip(1);
+    let games = match args.next() {
+        Some(arg) => arg.parse().expect("games must be a number"),
+        None => DEFAULT_GAMES,
+    };
+    let seed = match args.next() {
+        Some(arg) => arg.parse().expect("seed must be a number"),
+        None => fastrand::u64(..),
+    };
+    println!("{} games from seed {}", games, seed);
+
+    let mut results = [0; 3];
+    let mut plies = 0;
+    for game in 0..games {
+        let game_seed = seed.wrapping_add(game);
+        match panic::catch_unwind(|| play(game_seed)) {
+            Ok((state, length)) => {
+                let index = match state {
+                    GameState::Won(_) => 0,
+                    GameState::Draw => 1,
+                    GameState::Playing => 2,
+                };
+                results[index] += 1;
+                plies += length;
+            }
+            Err(_) => {
+                eprintln!(
+                    "game {} failed; replay with: selfplay 1 {}",
+                    game, game_seed
+                );
+                process::exit(1);
+            }
+        }
+    }
+    println!(
+        "{} won, {} drawn, {} drawn by the inactivity rule; {} plies in all",
+        results[0], results[1], results[2], plies
+    );
+}
+
+/// Plays one random game, panicking on the first broken invariant.
+/// Returns how it ended, `Playing` for the inactivity rule, and its length
+/// in plies.
+fn play(seed: u64) -> (GameState, usize) {
+    let mut rng = fastrand::Rng::with_seed(seed);
+    let mut board = Board::new();
+    let mut pieces = piece_count(&board);
+    let mut moves_played = Vec::new();
+    while board.state == GameState::Playing && !board.is_draw_by_inactivity() {
+        assert!(
+            moves_played.len() < MAX_PLIES,
+            "no end after {} plies",
+            MAX_PLIES
+        );
+        let moves = board.all_legal_moves();
+        assert!(!moves.is_empty(), "no legal moves but the game goes on");
+
+        // A random pair of squares is playable exactly when it is listed
+        let stray = (random_square(&mut rng), random_square(&mut rng));
+        let accepted = board.clone().try_move(stray.0, stray.1).is_ok();
+        assert_eq!(
+            accepted,
+            moves.contains(&stray),
+            "{} after {}",
+            notation::move_to_iccs(stray.0, stray.1),
+            moves_played.join(" ")
+        );
+
+        let (from, to) = moves[rng.usize(..moves.len())];
+        moves_played.push(notation::move_to_iccs(from, to));
+        let turn = board.turn;
+        if let Err(err) = board.try_move(from, to) {
+            panic!("legal move {} refused: {}", moves_played.join(" "), err);
+        }
+
+        if let Err(errors) = board.validate() {
+            panic!("invalid after {}: {:?}", moves_played.join(" "), errors);
+        }
+        assert_eq!(board.turn, turn.opposite(), "turn did not pass");
+        for color in [turn, turn.opposite()] {
+            assert!(
+                board.find_general(color).is_some(),
+                "{:?} General taken after {}",
+                color,
+                moves_played.join(" ")
+            );
+        }
+        let count = piece_count(&board);
+        assert!(
+            count == pieces || count + 1 == pieces,
+            "{} pieces became {} after {}",
+            pieces,
+            count,
+            moves_played.join(" ")
+        );
+        pieces = count;
+    }
+    (board.state, moves_played.len())
+}
+
+fn random_square(rng: &mut fastrand::Rng) -> Pos {
+    Pos::new(rng.usize(..WIDTH), rng.usize(..HEIGHT))
+}
+
+fn piece_count(board: &Board) -> usize {
+    board.grid.iter().flatten().flatten().count()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Environment variable with a seed to test from instead of `SEED`.
+    const SEED_ENV: &str = "SELFPLAY_SEED";
+    /// Any fixed value will do; it only keeps test runs repeatable.
+    const SEED: u64 = 1;
+    const GAMES: u64 = 20;
+
+    #[test]
+    fn random_games_keep_every_invariant_and_end() {
+        let seed = match std::env::var(SEED_ENV) {
+            Ok(value) => value.parse().expect("the seed must be a number"),
+            Err(_) => SEED,
+        };
+        for game in 0..GAMES {
+            let game_seed = seed.wrapping_add(game);
+            if panic::catch_unwind(|| play(game_seed)).is_err() {
+                panic!("game failed; replay with {}={}", SEED_ENV, game_seed);
+            }
+        }
+    }
+}
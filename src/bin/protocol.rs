@@ -0,0 +1,124 @@
+//! A small UCI-like text protocol over stdin and stdout, so that other
+//! programs can drive the engine without the GUI. One command per line:
+//!
+//! ```text
+//! position startpos [moves h2e2 h9g7 ...]
+//! position fen <FEN> [moves ...]
+//! go [depth N | movetime MS]
+//! isready
+//! quit
+//! ```
+//!
+//! `go` answers with `bestmove <iccs>`, or `bestmove (none)` when the side
+//! to move has no move. Depths over [`ai::MAX_SEARCH_DEPTH`] are refused. Problems are reported as `info string` lines. A
+//! `position` that is refused leaves no position at all, rather than the
+//! previous one, so `go` reports an error until a good `position` arrives.
+
+use std::io::{self, BufRead};
+use std::time::Duration;
+
+use chinese_chess::ai;
+use chinese_chess::game::{Board, GameState, Pos};
+use chinese_chess::notation;
+
+/// Depth searched by a bare `go`.
+const DEFAULT_DEPTH: u32 = 3;
+
+fn main() {
+    let mut board = Some(Board::new());
+    for line in io::stdin().lock().lines() {
+        let Ok(line) = line else { break };
+        let mut words = line.split_whitespace();
+        match words.next() {
+            Some("position") => match parse_position(words) {
+                Ok(position) => board = Some(position),
+                Err(err) => {
+                    board = None;
+                    println!("info string {}", err);
+                }
+            },
+            Some("go") => match go(board.as_ref(), words) {
+                Ok(Some((from, to))) => println!("bestmove {}", notation::move_to_iccs(from, to)),
+                Ok(None) => println!("bestmove (none)"),
+                Err(err) => println!("info string {}", err),
+            },
+            Some("isready") => println!("readyok"),
+            Some("quit") => break,
+            Some(command) => println!("info string unknown command '{}'", command),
+            None => {}
+        }
+    }
+}
+
+/// Reads the rest of a `position` command into a board.
+fn parse_position<'a>(mut words: impl Iterator<Item = &'a str>) -> Result<Board, String> {
+    let mut board = match words.next() {
+        Some("startpos") => Board::new(),
+        Some("fen") => {
+            let fen: Vec<&str> = words.by_ref().take_while(|&word| word != "moves").collect();
+            let board = Board::from_fen(&fen.join(" ")).map_err(|err| err.to_string())?;
+            // take_while already used up "moves"
+            return play_moves(board, words);
+        }
+        _ => return Err("expected 'startpos' or 'fen'".to_string()),
+    };
+    match words.next() {
+        Some("moves") => board = play_moves(board, words)?,
+        Some(word) => return Err(format!("expected 'moves', found '{}'", word)),
+        None => {}
+    }
+    Ok(board)
+}
+
+fn play_moves<'a>(mut board: Board, words: impl Iterator<Item = &'a str>) -> Result<Board, String> {
+    let mut moves = Vec::new();
+    for word in words {
+        moves.push(notation::iccs_to_move(word).ok_or(format!("bad move '{}'", word))?);
+    }
+    board.apply_moves(&moves).map_err(|(index, err)| {
+        format!(
+            "move {} '{}': {}",
+            index + 1,
+            notation::move_to_iccs(moves[index].0, moves[index].1),
+            err
+        )
+    })?;
+    Ok(board)
+}
+
+/// Searches as the rest of a `go` command asks.
+fn go<'a>(
+    board: Option<&Board>,
+    mut words: impl Iterator<Item = &'a str>,
+) -> Result<Option<(Pos, Pos)>, String> {
+    let limit = words.next();
+    let mut number = |name: &str| -> Result<u64, String> {
+        let value = words.next().ok_or(format!("{} needs a number", name))?;
+        value
+            .parse()
+            .map_err(|_| format!("bad {} '{}'", name, value))
+    };
+    let (depth, time) = match limit {
+        None => (DEFAULT_DEPTH, None),
+        Some("depth") => match number("depth")? {
+            depth if depth > u64::from(ai::MAX_SEARCH_DEPTH) => {
+                return Err(format!(
+                    "depth {} is deeper than the maximum of {}",
+                    depth,
+                    ai::MAX_SEARCH_DEPTH
+                ));
+            }
+            depth => (depth.max(1) as u32, None),
+        },
+        Some("movetime") => (0, Some(Duration::from_millis(number("movetime")?))),
+        Some(word) => return Err(format!("expected 'depth' or 'movetime', found '{}'", word)),
+    };
+    let board = board.ok_or("no position; the last 'position' was refused")?;
+    if board.state != GameState::Playing {
+        return Ok(None);
+    }
+    Ok(match time {
+        Some(time) => ai::search_for(board, board.turn, time),
+        None => ai::best_move(board, board.turn, depth),
+    })
+}
@@ -0,0 +1,222 @@
+use std::time::Duration;
+
+use crate::game::Color;
+
+/// A two-sided game clock. Elapsed time is fed in through [`Clock::tick`]
+/// rather than read from the wall clock, so callers decide where time comes
+/// from (frame deltas in the UI, fixed steps in tests).
+///
+/// Optionally the clock has byo-yomi overtime: once a side's main time is
+/// gone, each of its moves must be made within `period_millis`. A move
+/// that takes longer uses up one of the side's overtime periods, and the
+/// flag falls when the last period runs out.
+#[derive(Clone, Debug)]
+pub struct Clock {
+    pub red_remaining: Duration,
+    pub black_remaining: Duration,
+    /// Time added to a side's clock after each of its moves. Not credited
+    /// in overtime.
+    pub increment: Option<Duration>,
+    /// Side whose time is currently running, `None` once stopped.
+    pub running: Option<Color>,
+    pub red_overtime_periods: u32,
+    pub black_overtime_periods: u32,
+    /// Length of one overtime period; zero means no overtime.
+    pub period_millis: u64,
+    /// Time the running side has spent in its current overtime period.
+    period_used: Duration,
+}
+
+impl Clock {
+    /// Both sides start with `base`; Red's clock runs first.
+    pub fn new(base: Duration, increment: Option<Duration>) -> Self {
+        Self {
+            red_remaining: base,
+            black_remaining: base,
+            increment,
+            running: Some(Color::Red),
+            red_overtime_periods: 0,
+            black_overtime_periods: 0,
+            period_millis: 0,
+            period_used: Duration::ZERO,
+        }
+    }
+
+    /// Gives both sides `periods` overtime periods of `period_millis` each.
+    pub fn with_overtime(mut self, periods: u32, period_millis: u64) -> Self {
+        self.red_overtime_periods = periods;
+        self.black_overtime_periods = periods;
+        self.period_millis = period_millis;
+        self
+    }
+
+    pub fn remaining(&self, color: Color) -> Duration {
+        match color {
+            Color::Red => self.red_remaining,
+            Color::Black => self.black_remaining,
+        }
+    }
+
+    fn remaining_mut(&mut self, color: Color) -> &mut Duration {
+        match color {
+            Color::Red => &mut self.red_remaining,
+            Color::Black => &mut self.black_remaining,
+        }
+    }
+
+    pub fn overtime_periods(&self, color: Color) -> u32 {
+        match color {
+            Color::Red => self.red_overtime_periods,
+            Color::Black => self.black_overtime_periods,
+        }
+    }
+
+    fn overtime_periods_mut(&mut self, color: Color) -> &mut u32 {
+        match color {
+            Color::Red => &mut self.red_overtime_periods,
+            Color::Black => &mut self.black_overtime_periods,
+        }
+    }
+
+    /// Time left in the running side's current overtime period, or `None`
+    /// while it is still on main time.
+    pub fn period_remaining(&self) -> Option<Duration> {
+        let color = self.running?;
+        if !self.remaining(color).is_zero() || self.overtime_periods(color) == 0 {
+            return None;
+        }
+        Some(Duration::from_millis(self.period_millis).saturating_sub(self.period_used))
+    }
+
+    /// Charges `elapsed` to the running side, spilling into its overtime
+    /// periods once the main time is gone. Returns the side whose flag
+    /// fell, after which the clock is stopped.
+    pub fn tick(&mut self, elapsed: Duration) -> Option<Color> {
+        let color = self.running?;
+        let remaining = self.remaining_mut(color);
+        let overflow = elapsed.saturating_sub(*remaining);
+        *remaining = remaining.saturating_sub(elapsed);
+        if !remaining.is_zero() {
+            return None;
+        }
+
+        let period = Duration::from_millis(self.period_millis);
+        if period.is_zero() || self.overtime_periods(color) == 0 {
+            self.running = None;
+            return Some(color);
+        }
+        self.period_used += overflow;
+        while self.period_used >= period {
+            self.period_used -= period;
+            let periods = self.overtime_periods_mut(color);
+            *periods -= 1;
+            if *periods == 0 {
+                self.running = None;
+                return Some(color);
+            }
+        }
+        None
+    }
+
+    /// Called once `mover` has completed a move: credits the increment (or
+    /// resets the overtime period) and starts the opponent's clock.
+    pub fn switch(&mut self, mover: Color) {
+        if let Some(increment) = self.increment
+            && !self.remaining(mover).is_zero()
+        {
+            *self.remaining_mut(mover) += increment;
+        }
+        self.period_used = Duration::ZERO;
+        self.running = Some(mover.opposite());
+    }
+
+    pub fn stop(&mut self) {
+        self.running = None;
+    }
+}
+
+/// Formats a duration as `mm:ss`, rounding partial seconds up so a clock
+/// only shows `00:00` once it has actually run out.
+pub fn format_mm_ss(duration: Duration) -> String {
+    let secs = duration.as_millis().div_ceil(1000);
+    format!("{:02}:{:02}", secs / 60, secs % 60)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn secs(secs: u64) -> Duration {
+        Duration::from_secs(secs)
+    }
+
+    #[test]
+    fn tick_charges_only_the_running_side() {
+        let mut clock = Clock::new(secs(60), None);
+        assert_eq!(clock.tick(secs(10)), None);
+        assert_eq!(clock.remaining(Color::Red), secs(50));
+        assert_eq!(clock.remaining(Color::Black), secs(60));
+
+        clock.switch(Color::Red);
+        assert_eq!(clock.tick(secs(5)), None);
+        assert_eq!(clock.remaining(Color::Red), secs(50));
+        assert_eq!(clock.remaining(Color::Black), secs(55));
+    }
+
+    #[test]
+    fn increment_is_credited_to_the_mover() {
+        let mut clock = Clock::new(secs(60), Some(secs(3)));
+        clock.tick(secs(10));
+        clock.switch(Color::Red);
+        assert_eq!(clock.remaining(Color::Red), secs(53));
+        assert_eq!(clock.running, Some(Color::Black));
+    }
+
+    #[test]
+    fn flag_falls_when_main_time_runs_out() {
+        let mut clock = Clock::new(secs(5), None);
+        assert_eq!(clock.tick(secs(6)), Some(Color::Red));
+        assert_eq!(clock.remaining(Color::Red), Duration::ZERO);
+        assert_eq!(clock.running, None);
+        assert_eq!(clock.tick(secs(1)), None);
+    }
+
+    #[test]
+    fn format_rounds_partial_seconds_up() {
+        assert_eq!(format_mm_ss(Duration::from_millis(61_200)), "01:02");
+        assert_eq!(format_mm_ss(Duration::ZERO), "00:00");
+    }
+
+    #[test]
+    fn slow_overtime_move_consumes_a_period() {
+        let mut clock = Clock::new(secs(10), None).with_overtime(3, 30_000);
+        assert_eq!(clock.period_remaining(), None);
+        // Main time runs out 5 seconds into the first period
+        assert_eq!(clock.tick(secs(15)), None);
+        assert_eq!(clock.period_remaining(), Some(secs(25)));
+        assert_eq!(clock.overtime_periods(Color::Red), 3);
+
+        // Overrunning the period costs one and starts the next
+        assert_eq!(clock.tick(secs(30)), None);
+        assert_eq!(clock.overtime_periods(Color::Red), 2);
+        assert_eq!(clock.period_remaining(), Some(secs(25)));
+
+        // Moving within the period keeps the rest and starts a fresh one
+        clock.switch(Color::Red);
+        clock.tick(secs(1));
+        clock.switch(Color::Black);
+        assert_eq!(clock.overtime_periods(Color::Red), 2);
+        assert_eq!(clock.period_remaining(), Some(secs(30)));
+    }
+
+    #[test]
+    fn flag_falls_when_the_last_period_runs_out() {
+        let mut clock = Clock::new(secs(10), None).with_overtime(2, 30_000);
+        assert_eq!(clock.tick(secs(10 + 30)), None);
+        assert_eq!(clock.overtime_periods(Color::Red), 1);
+        assert_eq!(clock.tick(secs(29)), None);
+        assert_eq!(clock.tick(secs(1)), Some(Color::Red));
+        assert_eq!(clock.overtime_periods(Color::Red), 0);
+        assert_eq!(clock.running, None);
+    }
+}
@@ -0,0 +1,183 @@
+//! Short position codes: a compact alternative to FEN for sharing a
+//! position, e.g. by pasting it into a chat. Only the pieces and the side
+//! to move are kept, like [`Board::to_fen_short`].
+//!
+//! The bits, most significant first, are the side to move (1 for Black),
+//! one bit per square in FEN order saying whether it is occupied, then four
+//! bits per occupied square: the colour (1 for Black) and the
+//! `PieceType as u8`. They are written as unpadded base64url, 37 characters
+//! for the starting position against 61 for its short FEN. The last
+//! character is padded with zero bits, and nothing may follow it.
+
+use crate::game::{Board, BoardError, Color, HEIGHT, Piece, PieceType, Pos, WIDTH};
+
+const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789-_";
+
+const PIECE_TYPES: [PieceType; 7] = [
+    PieceType::General,
+    PieceType::Advisor,
+    PieceType::Elephant,
+    PieceType::Horse,
+    PieceType::Chariot,
+    PieceType::Cannon,
+    PieceType::Soldier,
+];
+
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub enum CodeError {
+    /// A character outside the base64url alphabet.
+    InvalidCharacter(char),
+    /// The code ends before every occupied square has its piece.
+    Truncated,
+    /// A piece type number with no piece type.
+    UnknownPiece(u8),
+    /// Characters after the last piece, or padding bits that are not zero.
+    TrailingData,
+    /// The code decoded but describes an impossible position.
+    Invalid(Vec<BoardError>),
+}
+
+impl std::fmt::Display for CodeError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            CodeError::InvalidCharacter(c) => write!(f, "invalid character '{}'", c),
+            CodeError::Truncated => write!(f, "the code is cut short"),
+            CodeError::UnknownPiece(n) => write!(f, "unknown piece type {}", n),
+            CodeError::TrailingData => write!(f, "the code goes on past the last piece"),
+            CodeError::Invalid(errors) => {
+                let messages: Vec<String> = errors.iter().map(|e| e.to_string()).collect();
+                write!(f, "{}", messages.join("; "))
+            }
+        }
+    }
+}
+
+impl std::error::Error for CodeError {}
+
+impl Board {
+    /// The position as a short code that [`Board::from_code`] reads back.
+    pub fn to_code(&self) -> String {
+        let mut bits = Vec::new();
+        bits.push(self.turn == Color::Black);
+        let pieces: Vec<Piece> = self.grid.iter().flatten().flatten().copied().collect();
+        bits.extend(self.grid.iter().flatten().map(Option::is_some));
+        for piece in pieces {
+            bits.push(piece.color == Color::Black);
+            let piece_type = piece.piece_type as u8;
+            bits.extend((0..3).rev().map(|shift| piece_type >> shift & 1 == 1));
+        }
+        bits.chunks(6)
+            .map(|chunk| {
+                let value = chunk
+                    .iter()
+                    .enumerate()
+                    .fold(0, |value, (i, &bit)| value | (bit as usize) << (5 - i));
+                ALPHABET[value] as char
+            })
+            .collect()
+    }
+
+    /// Reads a code written by [`Board::to_code`]. Like
+    /// [`Board::from_fen`], the position must be valid, and one where the
+    /// side to move has no legal move is loaded as won for the other side.
+    pub fn from_code(code: &str) -> Result<Board, CodeError> {
+        let mut bits = Vec::new();
+        for c in code.trim().chars() {
+            let value = ALPHABET
+                .iter()
+                .position(|&a| a as char == c)
+                .ok_or(CodeError::InvalidCharacter(c))?;
+            bits.extend((0..6).rev().map(|shift| value >> shift & 1 == 1));
+        }
+        let mut bits = bits.into_iter();
+        let mut next = || bits.next().ok_or(CodeError::Truncated);
+
+        let turn = if next()? { Color::Black } else { Color::Red };
+        let mut occupied = Vec::new();
+        for y in 0..HEIGHT {
+            for x in 0..WIDTH {
+                if next()? {
+                    occupied.push(Pos::new(x, y));
+                }
+            }
+        }
+        let mut pieces = Vec::new();
+        for pos in occupied {
+            let color = if next()? { Color::Black } else { Color::Red };
+            let mut piece_type = 0;
+            for _ in 0..3 {
+                piece_type = piece_type << 1 | next()? as u8;
+            }
+            let piece_type = *PIECE_TYPES
+                .get(piece_type as usize)
+                .ok_or(CodeError::UnknownPiece(piece_type))?;
+            pieces.push((pos, Piece { color, piece_type }));
+        }
+        // Only the zero padding of the last character may be left, so each
+        // position has exactly one code
+        let rest: Vec<bool> = bits.collect();
+        if rest.len() >= 6 || rest.contains(&true) {
+            return Err(CodeError::TrailingData);
+        }
+
+        // Every square is on the board and listed once, so this can't fail
+        let mut board =
+            Board::from_pieces(&pieces, turn).map_err(|err| CodeError::Invalid(vec![err]))?;
+        board.validate().map_err(CodeError::Invalid)?;
+        if board.all_legal_moves().is_empty() {
+            board.lose_without_moves();
+        }
+        Ok(board)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::notation;
+
+    #[test]
+    fn codes_round_trip_and_stay_short() {
+        let mut board = Board::new();
+        assert_eq!(board.to_code().len(), 37);
+        for iccs in ["h2e2", "h9g7", "e2e6", "g6g5", "e6a6"] {
+            let code = board.to_code();
+            // The start position has the most pieces, so the longest code
+            assert!(code.len() <= 37, "{}", code);
+            let decoded = Board::from_code(&code).unwrap();
+            assert_eq!(decoded.to_fen_short(), board.to_fen_short());
+            let (from, to) = notation::iccs_to_move(iccs).unwrap();
+            board.try_move(from, to).unwrap();
+        }
+        assert_eq!(board.grid.iter().flatten().flatten().count(), 30);
+        assert!(board.to_code().len() < 37);
+    }
+
+    #[test]
+    fn extra_characters_and_padding_bits_are_rejected() {
+        let code = Board::new().to_code();
+        assert_eq!(
+            Board::from_code(&format!("{}A", code)).err(),
+            Some(CodeError::TrailingData)
+        );
+        assert_eq!(
+            Board::from_code(&code[..code.len() - 1]).err(),
+            Some(CodeError::Truncated)
+        );
+
+        // 219 bits leave three bits of padding in the last character
+        let last = ALPHABET
+            .iter()
+            .position(|&c| c as char == code.chars().last().unwrap());
+        let padded = ALPHABET[last.unwrap() | 1] as char;
+        let changed = format!("{}{}", &code[..code.len() - 1], padded);
+        assert_eq!(
+            Board::from_code(&changed).err(),
+            Some(CodeError::TrailingData)
+        );
+        assert_eq!(
+            Board::from_code("$").err(),
+            Some(CodeError::InvalidCharacter('$'))
+        );
+    }
+}
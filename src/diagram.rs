@@ -0,0 +1,423 @@
+//! Static board diagrams, for illustrating games outside the app: SVG
+//! always, and PNG with the `png` feature. Both draw the same picture,
+//! with the last move marked by a line as the app marks it.
+
+use std::fmt::Write as _;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use crate::game::{Board, Color, Pos};
+
+/// Output format for [`Board::export_position_images`].
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum ImageFormat {
+    Svg,
+    /// Piece glyphs come from [`load_cjk_font`]; without one the pieces
+    /// are drawn as plain outlined discs.
+    #[cfg(feature = "png")]
+    Png,
+}
+
+impl ImageFormat {
+    pub fn extension(&self) -> &'static str {
+        match self {
+            ImageFormat::Svg => "svg",
+            #[cfg(feature = "png")]
+            ImageFormat::Png => "png",
+        }
+    }
+}
+
+const CELL: f32 = 50.0;
+const MARGIN: f32 = 40.0;
+const WIDTH: f32 = 2.0 * MARGIN + 8.0 * CELL;
+const HEIGHT: f32 = 2.0 * MARGIN + 9.0 * CELL;
+const PIECE_RADIUS: f32 = CELL * 0.4;
+const FONT_SIZE: f32 = CELL * 0.5;
+/// Last-move line: the app's default theme colour and stroke width.
+const LAST_MOVE_RGBA: [u8; 4] = [100, 100, 100, 120];
+const LAST_MOVE_WIDTH: f32 = CELL * 0.08;
+
+fn point(pos: Pos) -> (f32, f32) {
+    (MARGIN + pos.x as f32 * CELL, MARGIN + pos.y as f32 * CELL)
+}
+
+/// Every line of the empty board as (from, to) grid points.
+fn grid_lines() -> Vec<(Pos, Pos)> {
+    let mut lines = Vec::new();
+    // Ranks run across, files stop at the river except on the edges
+    for y in 0..10 {
+        lines.push((Pos::new(0, y), Pos::new(8, y)));
+    }
+    for x in 0..9 {
+        if x == 0 || x == 8 {
+            lines.push((Pos::new(x, 0), Pos::new(x, 9)));
+        } else {
+            lines.push((Pos::new(x, 0), Pos::new(x, 4)));
+            lines.push((Pos::new(x, 5), Pos::new(x, 9)));
+        }
+    }
+    // Palace diagonals
+    lines.push((Pos::new(3, 0), Pos::new(5, 2)));
+    lines.push((Pos::new(5, 0), Pos::new(3, 2)));
+    lines.push((Pos::new(3, 7), Pos::new(5, 9)));
+    lines.push((Pos::new(5, 7), Pos::new(3, 9)));
+    lines
+}
+
+/// Renders `board` as a standalone SVG document.
+pub fn to_svg(board: &Board) -> String {
+    let (width, height) = (WIDTH, HEIGHT);
+    let mut svg = String::new();
+    let _ = writeln!(
+        svg,
+        r#"<svg xmlns="http://www.w3.org/2000/svg" width="{width}" height="{height}" viewBox="0 0 {width} {height}">"#
+    );
+    let _ = writeln!(
+        svg,
+        r#"<rect width="{width}" height="{height}" fill="rgb(240,220,180)"/>"#
+    );
+
+    for (from, to) in grid_lines() {
+        let (x1, y1) = point(from);
+        let (x2, y2) = point(to);
+        let _ = writeln!(
+            svg,
+            r#"<line x1="{x1}" y1="{y1}" x2="{x2}" y2="{y2}" stroke="black" stroke-width="1"/>"#
+        );
+    }
+
+    for y in 0..10 {
+        for x in 0..9 {
+            let piece = match board.grid[y][x] {
+                Some(p) => p,
+                None => continue,
+            };
+            let (cx, cy) = point(Pos::new(x, y));
+            let color = match piece.color {
+                Color::Red => "red",
+                Color::Black => "black",
+            };
+            let (radius, font_size) = (PIECE_RADIUS, FONT_SIZE);
+            let _ = writeln!(
+                svg,
+                r#"<circle cx="{cx}" cy="{cy}" r="{radius}" fill="rgb(240,220,180)" stroke="{color}" stroke-width="2"/>"#
+            );
+            let _ = writeln!(
+                svg,
+                r#"<text x="{cx}" y="{cy}" font-size="{font_size}" text-anchor="middle" dominant-baseline="central" fill="{color}">{}</text>"#,
+                piece.glyph()
+            );
+        }
+    }
+
+    if let Some(last) = board.history.last() {
+        let (x1, y1) = point(last.from);
+        let (x2, y2) = point(last.to);
+        let [r, g, b, a] = LAST_MOVE_RGBA;
+        let opacity = a as f32 / 255.0;
+        let width = LAST_MOVE_WIDTH;
+        let _ = writeln!(
+            svg,
+            r#"<line x1="{x1}" y1="{y1}" x2="{x2}" y2="{y2}" stroke="rgb({r},{g},{b})" stroke-opacity="{opacity}" stroke-width="{width}"/>"#
+        );
+    }
+
+    svg.push_str("</svg>\n");
+    svg
+}
+
+impl Board {
+    /// Writes every position of the game so far into `dir` as numbered
+    /// images (`position_000.svg`, `position_001.svg`, ...), starting with
+    /// the initial position. Returns the number of files written.
+    pub fn export_position_images(&self, dir: &Path, format: ImageFormat) -> io::Result<usize> {
+        fs::create_dir_all(dir)?;
+        #[cfg(feature = "png")]
+        let font = match format {
+            ImageFormat::Png => load_cjk_font().ok(),
+            ImageFormat::Svg => None,
+        };
+        let positions = self.positions();
+        for (i, position) in positions.iter().enumerate() {
+            let path = dir.join(format!("position_{:03}.{}", i, format.extension()));
+            let contents = match format {
+                ImageFormat::Svg => to_svg(position).into_bytes(),
+                #[cfg(feature = "png")]
+                ImageFormat::Png => to_png(position, font.as_deref()),
+            };
+            fs::write(path, contents)?;
+        }
+        Ok(positions.len())
+    }
+}
+
+/// Environment variable naming a font file to use instead of the system
+/// CJK fonts below.
+pub const FONT_ENV: &str = "CHINESE_CHESS_FONT";
+
+/// Common locations of a font with the piece glyphs, tried in order.
+#[cfg(feature = "png")]
+const CJK_FONT_PATHS: [&str; 8] = [
+    "/usr/share/fonts/opentype/noto/NotoSansCJK-Regular.ttc",
+    "/usr/share/fonts/noto-cjk/NotoSansCJK-Regular.ttc",
+    "/usr/share/fonts/truetype/wqy/wqy-microhei.ttc",
+    "/System/Library/Fonts/PingFang.ttc",
+    "/System/Library/Fonts/STHeiti Light.ttc",
+    "/Library/Fonts/Arial Unicode.ttf",
+    "C:\\Windows\\Fonts\\msyh.ttc",
+    "C:\\Windows\\Fonts\\simsun.ttc",
+];
+
+/// Reads the first usable CJK font, from `FONT_ENV` or `CJK_FONT_PATHS`.
+/// Files that are missing or don't parse as a font are skipped, since
+/// egui panics on bad font data. When none is usable, says why each file
+/// that exists was skipped.
+#[cfg(feature = "png")]
+pub fn load_cjk_font() -> Result<Vec<u8>, Vec<String>> {
+    let from_env = std::env::var(FONT_ENV).ok();
+    let mut skipped = Vec::new();
+    for path in from_env.iter().map(String::as_str).chain(CJK_FONT_PATHS) {
+        let Ok(data) = fs::read(path) else {
+            continue;
+        };
+        match ab_glyph::FontRef::try_from_slice(&data) {
+            Ok(_) => return Ok(data),
+            Err(err) => skipped.push(format!("skipping font {}: {}", path, err)),
+        }
+    }
+    Err(skipped)
+}
+
+/// Renders `board` as an RGBA image of the same picture as [`to_svg`],
+/// with piece glyphs from `font` when given.
+#[cfg(feature = "png")]
+pub fn to_image(board: &Board, font: Option<&[u8]>) -> image::RgbaImage {
+    use tiny_skia::{FillRule, Paint, PathBuilder, Pixmap, Stroke, Transform};
+
+    let background = tiny_skia::Color::from_rgba8(240, 220, 180, 255);
+    let mut pixmap = Pixmap::new(WIDTH as u32, HEIGHT as u32).expect("diagram size is not zero");
+    pixmap.fill(background);
+    let paint = |color: tiny_skia::Color| {
+        let mut paint = Paint::default();
+        paint.set_color(color);
+        paint
+    };
+    let stroke = |width| Stroke {
+        width,
+        ..Stroke::default()
+    };
+
+    let mut lines = PathBuilder::new();
+    for (from, to) in grid_lines() {
+        let (x1, y1) = point(from);
+        let (x2, y2) = point(to);
+        lines.move_to(x1, y1);
+        lines.line_to(x2, y2);
+    }
+    if let Some(path) = lines.finish() {
+        pixmap.stroke_path(
+            &path,
+            &paint(tiny_skia::Color::BLACK),
+            &stroke(1.0),
+            Transform::identity(),
+            None,
+        );
+    }
+
+    let font = font.and_then(|data| ab_glyph::FontRef::try_from_slice(data).ok());
+    for y in 0..10 {
+        for x in 0..9 {
+            let Some(piece) = board.grid[y][x] else {
+                continue;
+            };
+            let (cx, cy) = point(Pos::new(x, y));
+            let color = match piece.color {
+                Color::Red => [255, 0, 0],
+                Color::Black => [0, 0, 0],
+            };
+            let Some(disc) = PathBuilder::from_circle(cx, cy, PIECE_RADIUS) else {
+                continue;
+            };
+            pixmap.fill_path(
+                &disc,
+                &paint(background),
+                FillRule::Winding,
+                Transform::identity(),
+                None,
+            );
+            pixmap.stroke_path(
+                &disc,
+                &paint(tiny_skia::Color::from_rgba8(
+                    color[0], color[1], color[2], 255,
+                )),
+                &stroke(2.0),
+                Transform::identity(),
+                None,
+            );
+            if let Some(font) = &font {
+                let glyph = piece.glyph().chars().next().unwrap_or(' ');
+                draw_glyph(&mut pixmap, font, glyph, (cx, cy), color);
+            }
+        }
+    }
+
+    if let Some(last) = board.history.last() {
+        let (x1, y1) = point(last.from);
+        let (x2, y2) = point(last.to);
+        let [r, g, b, a] = LAST_MOVE_RGBA;
+        let mut line = PathBuilder::new();
+        line.move_to(x1, y1);
+        line.line_to(x2, y2);
+        if let Some(line) = line.finish() {
+            pixmap.stroke_path(
+                &line,
+                &paint(tiny_skia::Color::from_rgba8(r, g, b, a)),
+                &stroke(LAST_MOVE_WIDTH),
+                Transform::identity(),
+                None,
+            );
+        }
+    }
+
+    // Every pixel is opaque, so the premultiplied data is plain RGBA
+    image::RgbaImage::from_raw(pixmap.width(), pixmap.height(), pixmap.take())
+        .expect("pixmap data matches its size")
+}
+
+/// [`to_image`] encoded as a PNG file.
+#[cfg(feature = "png")]
+pub fn to_png(board: &Board, font: Option<&[u8]>) -> Vec<u8> {
+    let mut png = Vec::new();
+    to_image(board, font)
+        .write_to(&mut io::Cursor::new(&mut png), image::ImageFormat::Png)
+        .expect("encoding to memory does not fail");
+    png
+}
+
+/// Blends `glyph` in `color` onto the opaque `pixmap`, centred on `center`.
+/// Glyphs the font lacks are left out.
+#[cfg(feature = "png")]
+fn draw_glyph(
+    pixmap: &mut tiny_skia::Pixmap,
+    font: &ab_glyph::FontRef,
+    glyph: char,
+    center: (f32, f32),
+    color: [u8; 3],
+) {
+    use ab_glyph::{Font, PxScale};
+
+    let id = font.glyph_id(glyph);
+    if id.0 == 0 {
+        return;
+    }
+    // FONT_SIZE is the em size, as in the SVG; ab_glyph scales by height
+    let em = font.units_per_em().unwrap_or(1000.0);
+    let scale = PxScale::from(FONT_SIZE * font.height_unscaled() / em);
+    let Some(outline) = font.outline_glyph(id.with_scale(scale)) else {
+        return;
+    };
+    let bounds = outline.px_bounds();
+    let left = (center.0 - bounds.width() / 2.0).round() as i64;
+    let top = (center.1 - bounds.height() / 2.0).round() as i64;
+    let width = pixmap.width() as i64;
+    let height = pixmap.height() as i64;
+    let data = pixmap.data_mut();
+    outline.draw(|x, y, coverage| {
+        let (px, py) = (left + x as i64, top + y as i64);
+        if px < 0 || py < 0 || px >= width || py >= height {
+            return;
+        }
+        let index = ((py * width + px) * 4) as usize;
+        let coverage = coverage.clamp(0.0, 1.0);
+        for (channel, &value) in data[index..index + 3].iter_mut().zip(&color) {
+            *channel = (*channel as f32 * (1.0 - coverage) + value as f32 * coverage).round() as u8;
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::notation;
+
+    #[test]
+    fn one_non_empty_image_per_position() {
+        let mut board = Board::new();
+        for iccs in ["h2e2", "h9g7", "h0g2"] {
+            let (from, to) = notation::iccs_to_move(iccs).unwrap();
+            board.try_move(from, to).unwrap();
+        }
+        let formats = [
+            ImageFormat::Svg,
+            #[cfg(feature = "png")]
+            ImageFormat::Png,
+        ];
+
+        for format in formats {
+            let dir = std::env::temp_dir().join(format!(
+                "diagram-{}-{}",
+                std::process::id(),
+                format.extension()
+            ));
+            let _ = fs::remove_dir_all(&dir);
+            assert_eq!(board.export_position_images(&dir, format).unwrap(), 4);
+
+            let mut files: Vec<_> = fs::read_dir(&dir)
+                .unwrap()
+                .map(|entry| entry.unwrap().path())
+                .collect();
+            files.sort();
+            for path in &files {
+                assert!(fs::metadata(path).unwrap().len() > 0, "{:?} is empty", path);
+            }
+            fs::remove_dir_all(&dir).unwrap();
+            let names: Vec<_> = files
+                .iter()
+                .map(|path| path.file_name().unwrap().to_string_lossy().into_owned())
+                .collect();
+            let expected: Vec<_> = (0..4)
+                .map(|i| format!("position_{:03}.{}", i, format.extension()))
+                .collect();
+            assert_eq!(names, expected);
+        }
+    }
+
+    #[cfg(feature = "png")]
+    #[test]
+    fn png_has_the_diagram_size_and_decodes() {
+        let png = to_png(&Board::new(), None);
+        assert!(!png.is_empty());
+        let decoded = image::load_from_memory(&png).unwrap();
+        assert_eq!(decoded.width(), WIDTH as u32);
+        assert_eq!(decoded.height(), HEIGHT as u32);
+
+        // Background in a corner, a grid line at a9, a piece outline on e0
+        let image = decoded.to_rgba8();
+        assert_eq!(image.get_pixel(2, 2).0, [240, 220, 180, 255]);
+        assert_eq!(image.get_pixel(MARGIN as u32, 5).0, [240, 220, 180, 255]);
+        let (x, y) = point(Pos::new(4, 9));
+        let rim = image.get_pixel(x as u32, (y - PIECE_RADIUS) as u32).0;
+        assert!(rim[0] > 200 && rim[1] < 100, "{:?}", rim);
+    }
+
+    #[cfg(feature = "png")]
+    #[test]
+    fn image_marks_the_last_move() {
+        let mut board = Board::new();
+        let start = to_image(&board, None);
+        assert_eq!(start.dimensions(), (WIDTH as u32, HEIGHT as u32));
+        assert!(start.pixels().any(|pixel| pixel.0 != [240, 220, 180, 255]));
+
+        // Just off the grid line along h2e2, wider than the line itself
+        board.try_move(Pos::new(7, 7), Pos::new(4, 7)).unwrap();
+        let moved = to_image(&board, None);
+        let (x, y) = point(Pos::new(6, 7));
+        let (x, y) = (x as u32 + CELL as u32 / 2, y as u32 + 1);
+        assert_eq!(start.get_pixel(x, y).0, [240, 220, 180, 255]);
+        let mark = moved.get_pixel(x, y).0;
+        assert!(mark[0] < 240, "{:?}", mark);
+        assert!(to_svg(&board).contains("stroke-opacity"));
+    }
+}
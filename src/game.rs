@@ -1,9 +1,25 @@
-#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+use std::collections::HashMap;
+
+use crate::notation;
+
+#[derive(Clone, Copy, PartialEq, Eq, Default, Debug)]
 pub enum Color {
+    /// Moves first.
+    #[default]
     Red,
     Black,
 }
 
+/// `紅` or `黑`.
+impl std::fmt::Display for Color {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Color::Red => "紅",
+            Color::Black => "黑",
+        })
+    }
+}
+
 impl Color {
     pub fn opposite(&self) -> Self {
         match self {
@@ -13,7 +29,7 @@ impl Color {
     }
 }
 
-#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
 pub enum PieceType {
     General,  // King/Shuai/Jiang
     Advisor,  // Guard/Shi
@@ -24,42 +40,439 @@ pub enum PieceType {
     Soldier,  // Pawn/Bing/Zu
 }
 
+/// The piece's usual name, written as on Black's pieces: `車` for a Chariot
+/// of either side. [`Piece`] displays the character of the right side.
+impl std::fmt::Display for PieceType {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let piece = Piece {
+            color: Color::Black,
+            piece_type: *self,
+        };
+        f.write_str(piece.glyph())
+    }
+}
+
 #[derive(Clone, Copy, PartialEq, Eq, Debug)]
 pub struct Piece {
     pub color: Color,
     pub piece_type: PieceType,
 }
 
+impl Piece {
+    /// FEN letter for this piece: uppercase for Red, lowercase for Black.
+    pub fn fen_char(&self) -> char {
+        let c = match self.piece_type {
+            PieceType::General => 'k',
+            PieceType::Advisor => 'a',
+            PieceType::Elephant => 'b',
+            PieceType::Horse => 'n',
+            PieceType::Chariot => 'r',
+            PieceType::Cannon => 'c',
+            PieceType::Soldier => 'p',
+        };
+        match self.color {
+            Color::Red => c.to_ascii_uppercase(),
+            Color::Black => c,
+        }
+    }
+
+    /// Traditional character for the piece, e.g. `帥` for the Red General.
+    pub fn glyph(&self) -> &'static str {
+        match (self.color, self.piece_type) {
+            (Color::Red, PieceType::General) => "帥",
+            (Color::Red, PieceType::Advisor) => "仕",
+            (Color::Red, PieceType::Elephant) => "相",
+            (Color::Red, PieceType::Horse) => "傌",
+            (Color::Red, PieceType::Chariot) => "俥",
+            (Color::Red, PieceType::Cannon) => "炮",
+            (Color::Red, PieceType::Soldier) => "兵",
+            (Color::Black, PieceType::General) => "將",
+            (Color::Black, PieceType::Advisor) => "士",
+            (Color::Black, PieceType::Elephant) => "象",
+            (Color::Black, PieceType::Horse) => "馬",
+            (Color::Black, PieceType::Chariot) => "車",
+            (Color::Black, PieceType::Cannon) => "砲",
+            (Color::Black, PieceType::Soldier) => "卒",
+        }
+    }
+
+    /// Inverse of [`Piece::fen_char`]. Accepts both the `b`/`n` and the
+    /// `e`/`h` spellings for Elephant and Horse.
+    pub fn from_fen_char(c: char) -> Option<Piece> {
+        let piece_type = match c.to_ascii_lowercase() {
+            'k' => PieceType::General,
+            'a' => PieceType::Advisor,
+            'b' | 'e' => PieceType::Elephant,
+            'n' | 'h' => PieceType::Horse,
+            'r' => PieceType::Chariot,
+            'c' => PieceType::Cannon,
+            'p' => PieceType::Soldier,
+            _ => return None,
+        };
+        let color = if c.is_ascii_uppercase() {
+            Color::Red
+        } else {
+            Color::Black
+        };
+        Some(Piece { color, piece_type })
+    }
+}
+
+/// Same as [`Piece::glyph`].
+impl std::fmt::Display for Piece {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.glyph())
+    }
+}
+
 #[derive(Clone, Copy, PartialEq, Eq, Debug)]
 pub struct Pos {
     pub x: usize,
     pub y: usize,
 }
 
+/// Files on the board, a to i.
+pub const WIDTH: usize = 9;
+/// Ranks on the board, 0 to 9.
+pub const HEIGHT: usize = 10;
+
 impl Pos {
     pub fn new(x: usize, y: usize) -> Self {
         Self { x, y }
     }
+
+    /// The square at `(x, y)`, or `None` if that is off the board.
+    pub fn try_new(x: i32, y: i32) -> Option<Pos> {
+        let pos = Pos::new(usize::try_from(x).ok()?, usize::try_from(y).ok()?);
+        pos.in_bounds().then_some(pos)
+    }
+
+    pub fn in_bounds(&self) -> bool {
+        self.x < WIDTH && self.y < HEIGHT
+    }
+}
+
+/// ICCS coordinate, e.g. `e3`. Squares off the board, which have none,
+/// show as `(x, y)`.
+impl std::fmt::Display for Pos {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.in_bounds() {
+            f.write_str(&notation::pos_to_iccs(*self))
+        } else {
+            write!(f, "({}, {})", self.x, self.y)
+        }
+    }
 }
 
 #[derive(Clone, Copy, PartialEq, Eq, Debug)]
 pub enum GameState {
     Playing,
     Won(Color),
+    Draw,
+}
+
+/// How a won game was won, kept in [`Board::result_reason`] beside
+/// [`GameState::Won`].
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum WinReason {
+    /// The loser is in check with no legal move.
+    Checkmate,
+    /// The loser has no legal move but is not in check, which also loses.
+    Stalemate,
+    Resignation,
+    /// The loser's clock ran out.
+    Timeout,
+}
+
+impl Default for Board {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
+#[derive(Clone)]
 pub struct Board {
-    pub grid: [[Option<Piece>; 9]; 10],
+    pub grid: [[Option<Piece>; WIDTH]; HEIGHT],
     pub turn: Color,
     pub selected: Option<Pos>,
     pub state: GameState,
+    /// Half-moves since the last capture.
+    pub halfmove_clock: u32,
+    /// Starts at 1 and increments after each Black move.
+    pub fullmove_number: u32,
+    /// Half-moves without a capture after which the game counts as drawn by
+    /// inactivity.
+    pub inactivity_limit: u32,
+    /// Moves played so far, oldest first.
+    pub history: Vec<HistoryEntry>,
+    /// Side with a pending draw offer. The offer lapses once the other
+    /// side moves instead of answering it.
+    pub draw_offer: Option<Color>,
+    /// Tournament move-counting rule, off unless set.
+    pub counting: Option<CountingRules>,
+    /// How the game was won, set together with [`GameState::Won`].
+    pub result_reason: Option<WinReason>,
+    on_event: EventHandler,
+}
+
+/// Something that happened in a game, reported to the handler set with
+/// [`Board::set_event_handler`].
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Event {
+    Moved {
+        from: Pos,
+        to: Pos,
+    },
+    Captured(Piece),
+    /// This side's General is attacked after the move.
+    Check(Color),
+    /// The game has just ended this way.
+    GameOver(GameState),
+}
+
+/// The callback behind [`Board::set_event_handler`]. Copies of a board
+/// start without one, so search and analysis on copies stay silent.
+#[derive(Default)]
+struct EventHandler(Option<Box<dyn FnMut(Event) + Send>>);
+
+impl Clone for EventHandler {
+    fn clone(&self) -> Self {
+        Self(None)
+    }
+}
+
+pub const DEFAULT_INACTIVITY_LIMIT: u32 = 120;
+
+/// Move-counting rule some Asian tournaments use to end endgames that are
+/// won on paper but going nowhere. Once the attacking pieces (Chariots,
+/// Horses, Cannons and Soldiers) of both sides together number at most
+/// `material_threshold`, the side with more of them must capture or mate
+/// within `move_limit` of its own moves, or the game is drawn.
+///
+/// Material only drops with a capture, and a capture restarts the count, so
+/// the count is read off the halfmove clock and undo needs nothing extra.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct CountingRules {
+    pub material_threshold: u32,
+    pub move_limit: u32,
+}
+
+impl Default for CountingRules {
+    fn default() -> Self {
+        Self {
+            material_threshold: 2,
+            move_limit: 30,
+        }
+    }
+}
+
+/// Everything about a game that [`Board::restore`] needs to continue it
+/// exactly, including the history that undo relies on. The selection is
+/// interface state and is left out.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct BoardSnapshot {
+    pub grid: [[Option<Piece>; WIDTH]; HEIGHT],
+    pub turn: Color,
+    pub state: GameState,
+    pub halfmove_clock: u32,
+    pub fullmove_number: u32,
+    pub inactivity_limit: u32,
+    pub history: Vec<HistoryEntry>,
+    pub draw_offer: Option<Color>,
+    pub counting: Option<CountingRules>,
+    pub result_reason: Option<WinReason>,
 }
 
+/// A played move, with what is needed to take it back.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct HistoryEntry {
+    pub from: Pos,
+    pub to: Pos,
+    pub captured: Option<Piece>,
+    /// Halfmove clock before the move was played.
+    pub halfmove_clock: u32,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum BoardError {
+    /// Two pieces were placed on the same square.
+    Occupied(Pos),
+    OutOfBounds(Pos),
+    MissingGeneral(Color),
+    /// More pieces of this type than a side starts with.
+    TooManyPieces(Color, PieceType),
+    GeneralOutsidePalace(Pos),
+    AdvisorOffSquare(Pos),
+    ElephantAcrossRiver(Pos),
+    ElephantOffSquare(Pos),
+    GeneralsFacing,
+    /// The side that just moved is in check, which no legal move allows.
+    OpponentInCheck,
+}
+
+impl std::fmt::Display for BoardError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            BoardError::Occupied(pos) => write!(f, "more than one piece on {}", pos),
+            BoardError::OutOfBounds(pos) => write!(f, "{} is off the board", pos),
+            BoardError::MissingGeneral(color) => write!(f, "{:?} has no General", color),
+            BoardError::TooManyPieces(color, piece_type) => {
+                write!(f, "{:?} has too many {:?} pieces", color, piece_type)
+            }
+            BoardError::GeneralOutsidePalace(pos) => {
+                write!(f, "General at {} is outside its palace", pos)
+            }
+            BoardError::AdvisorOffSquare(pos) => {
+                write!(f, "Advisor at {} is not on a palace diagonal point", pos)
+            }
+            BoardError::ElephantAcrossRiver(pos) => {
+                write!(f, "Elephant at {} has crossed the river", pos)
+            }
+            BoardError::ElephantOffSquare(pos) => {
+                write!(f, "Elephant at {} is not on an elephant point", pos)
+            }
+            BoardError::GeneralsFacing => write!(f, "the Generals face each other"),
+            BoardError::OpponentInCheck => write!(f, "the side not to move is in check"),
+        }
+    }
+}
+
+impl std::error::Error for BoardError {}
+
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub enum FenError {
+    /// The placement field does not have exactly ten ranks.
+    RankCount(usize),
+    /// The rank (0 = Black's back rank) does not describe exactly nine files.
+    RankWidth(usize),
+    UnknownPiece(char),
+    MissingSideToMove,
+    InvalidSideToMove(String),
+    InvalidCounter(String),
+    /// The FEN parsed but describes an impossible position.
+    Invalid(Vec<BoardError>),
+}
+
+impl std::fmt::Display for FenError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            FenError::RankCount(n) => write!(f, "expected 10 ranks, found {}", n),
+            FenError::RankWidth(rank) => write!(f, "rank {} does not have 9 files", rank),
+            FenError::UnknownPiece(c) => write!(f, "unknown piece '{}'", c),
+            FenError::MissingSideToMove => write!(f, "missing side to move"),
+            FenError::InvalidSideToMove(s) => write!(f, "invalid side to move '{}'", s),
+            FenError::InvalidCounter(s) => write!(f, "invalid move counter '{}'", s),
+            FenError::Invalid(errors) => {
+                let messages: Vec<String> = errors.iter().map(|e| e.to_string()).collect();
+                write!(f, "{}", messages.join("; "))
+            }
+        }
+    }
+}
+
+impl std::error::Error for FenError {}
+
+/// Starting setups offered by [`Board::new_variant`].
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum VariantKind {
+    Standard,
+    /// The Chariots, Horses and Cannons placed at random, the same way for
+    /// both sides and symmetric about the centre file.
+    SymmetricRandom,
+}
+
+impl VariantKind {
+    pub const ALL: [VariantKind; 2] = [VariantKind::Standard, VariantKind::SymmetricRandom];
+}
+
+/// How a position came to repeat, from [`Board::repetition_kind`]. Under the
+/// common Asian rules the side that keeps checking or chasing must vary its
+/// moves or lose; any other repetition is a draw.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum RepetitionKind {
+    /// This side gave check with every one of its moves in the cycle.
+    PerpetualCheck(Color),
+    /// This side threatened an undefended piece with every move in the
+    /// cycle that wasn't a check.
+    PerpetualChase(Color),
+    NeutralDraw,
+}
+
+/// Occurrences of a position, the current one included, at which
+/// [`Board::repetition_kind`] starts judging the repetition.
+pub const REPETITION_COUNT: u32 = 3;
+
+/// Why [`Board::try_move`] refused a move: the first rule it breaks, as
+/// reported by [`Board::explain_move`].
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum MoveError {
+    GameOver,
+    NoPiece(Pos),
+    /// The piece on the square belongs to the side not to move.
+    NotYourTurn(Pos),
+    /// The piece cannot move that way.
+    InvalidMove,
+    /// The target square holds a piece of the mover's own side.
+    CapturesOwnPiece(Pos),
+    /// A General or Advisor would leave its palace.
+    LeavesPalace,
+    /// An Elephant would cross the river.
+    CrossesRiver,
+    /// A Chariot's or Cannon's line is blocked by the piece at `at`.
+    BlockedPath {
+        at: Pos,
+    },
+    HorseLegBlocked {
+        at: Pos,
+    },
+    ElephantEyeBlocked {
+        at: Pos,
+    },
+    /// A Cannon capture with nothing to jump over.
+    NoScreen,
+    /// The move would leave the mover's own General in check.
+    LeavesGeneralInCheck,
+    /// The move would take the enemy General. Games end by mate, so this
+    /// only comes up in a position where the side not to move is already in
+    /// check, which a validated board never has.
+    CapturesGeneral,
+}
+
+impl std::fmt::Display for MoveError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match *self {
+            MoveError::GameOver => write!(f, "the game is over"),
+            MoveError::NoPiece(pos) => write!(f, "no piece on {}", pos),
+            MoveError::NotYourTurn(pos) => {
+                write!(f, "the piece on {} is not the side to move's", pos)
+            }
+            MoveError::InvalidMove => write!(f, "the piece cannot move there"),
+            MoveError::CapturesOwnPiece(pos) => {
+                write!(f, "{} holds a piece of the same side", pos)
+            }
+            MoveError::LeavesPalace => write!(f, "the piece may not leave the palace"),
+            MoveError::CrossesRiver => write!(f, "an Elephant may not cross the river"),
+            MoveError::BlockedPath { at } => write!(f, "the way is blocked at {}", at),
+            MoveError::HorseLegBlocked { at } => {
+                write!(f, "the Horse's leg is blocked at {}", at)
+            }
+            MoveError::ElephantEyeBlocked { at } => {
+                write!(f, "the Elephant's eye is blocked at {}", at)
+            }
+            MoveError::NoScreen => write!(f, "a Cannon needs a piece to jump over to capture"),
+            MoveError::LeavesGeneralInCheck => write!(f, "the move leaves the General in check"),
+            MoveError::CapturesGeneral => write!(f, "a General is never captured"),
+        }
+    }
+}
+
+impl std::error::Error for MoveError {}
+
 impl Board {
     pub fn new() -> Self {
-        let mut grid = [[None; 9]; 10];
+        let mut grid = [[None; WIDTH]; HEIGHT];
 
-        let setup_row = |grid: &mut [[Option<Piece>; 9]; 10], y: usize, color: Color| {
+        let setup_row = |grid: &mut [[Option<Piece>; WIDTH]; HEIGHT], y: usize, color: Color| {
             let pieces = [
                 PieceType::Chariot,
                 PieceType::Horse,
@@ -89,7 +502,7 @@ impl Board {
             color: Color::Black,
             piece_type: PieceType::Cannon,
         });
-        for x in (0..9).step_by(2) {
+        for x in (0..WIDTH).step_by(2) {
             grid[3][x] = Some(Piece {
                 color: Color::Black,
                 piece_type: PieceType::Soldier,
@@ -106,234 +519,2090 @@ impl Board {
             color: Color::Red,
             piece_type: PieceType::Cannon,
         });
-        for x in (0..9).step_by(2) {
+        for x in (0..WIDTH).step_by(2) {
             grid[6][x] = Some(Piece {
                 color: Color::Red,
                 piece_type: PieceType::Soldier,
             });
         }
 
-        Self {
-            grid,
-            turn: Color::Red,
-            selected: None,
-            state: GameState::Playing,
-        }
+        Self::with_grid(grid, Color::Red)
     }
 
-    pub fn get_piece(&self, pos: Pos) -> Option<Piece> {
-        if pos.x < 9 && pos.y < 10 {
-            self.grid[pos.y][pos.x]
-        } else {
-            None
-        }
+    /// The starting position with pieces taken off for odds play; see
+    /// [`Board::remove_handicap`].
+    pub fn new_with_handicap(red_remove: &[PieceType], black_remove: &[PieceType]) -> Board {
+        let mut board = Board::new();
+        board.remove_handicap(red_remove, black_remove);
+        board
     }
 
-    pub fn move_piece(&mut self, from: Pos, to: Pos) -> bool {
-        if self.state != GameState::Playing {
-            return false;
-        }
-        if let Some(piece) = self.get_piece(from) {
-            if piece.color != self.turn {
-                return false;
-            }
-            if self.is_valid_move(from, to) {
-                if let Some(target) = self.get_piece(to) {
-                    if target.piece_type == PieceType::General {
-                        self.state = GameState::Won(self.turn);
-                    }
+    /// Takes pieces off for odds play. Each listed type removes one more
+    /// piece of that type, starting from the owner's left: Red's left is
+    /// file a, Black's is file i. Types with none left, and the General,
+    /// are ignored.
+    pub fn remove_handicap(&mut self, red_remove: &[PieceType], black_remove: &[PieceType]) {
+        for (color, remove) in [(Color::Red, red_remove), (Color::Black, black_remove)] {
+            for &piece_type in remove {
+                if piece_type == PieceType::General {
+                    continue;
                 }
-
-                self.grid[to.y][to.x] = self.grid[from.y][from.x];
-                self.grid[from.y][from.x] = None;
-
-                if self.state == GameState::Playing {
-                    self.turn = self.turn.opposite();
+                let target = Some(Piece { color, piece_type });
+                let found = (0..HEIGHT)
+                    .flat_map(|y| (0..WIDTH).map(move |x| Pos::new(x, y)))
+                    // Scan in the owner's reading order: rank by rank from
+                    // its own back rank, left to right as it sees the board
+                    .map(|pos| match color {
+                        Color::Red => Pos::new(pos.x, 9 - pos.y),
+                        Color::Black => Pos::new(8 - pos.x, pos.y),
+                    })
+                    .find(|&pos| self.grid[pos.y][pos.x] == target);
+                if let Some(pos) = found {
+                    self.grid[pos.y][pos.x] = None;
                 }
-                return true;
             }
         }
-        false
     }
 
-    fn is_valid_move(&self, from: Pos, to: Pos) -> bool {
-        if from == to {
-            return false;
-        }
-        if to.x >= 9 || to.y >= 10 {
-            return false;
+    /// A starting position of the given kind, with Red to move.
+    pub fn new_variant(kind: VariantKind) -> Board {
+        let mut board = Board::new();
+        if kind == VariantKind::SymmetricRandom {
+            // Only pieces free of placement rules move: the Chariot and
+            // Horse swap corners, and the Cannons change files. Both sides
+            // get the same setup, mirrored about the centre file.
+            let corner = if fastrand::bool() {
+                [PieceType::Chariot, PieceType::Horse]
+            } else {
+                [PieceType::Horse, PieceType::Chariot]
+            };
+            let cannon_x = fastrand::usize(0..4);
+            for (color, back, cannons) in [(Color::Red, 9, 7), (Color::Black, 0, 2)] {
+                for (x, &piece_type) in corner.iter().enumerate() {
+                    let piece = Some(Piece { color, piece_type });
+                    board.grid[back][x] = piece;
+                    board.grid[back][WIDTH - 1 - x] = piece;
+                }
+                let cannon = board.grid[cannons][1].take();
+                board.grid[cannons][WIDTH - 2] = None;
+                board.grid[cannons][cannon_x] = cannon;
+                board.grid[cannons][WIDTH - 1 - cannon_x] = cannon;
+            }
         }
+        board
+    }
 
-        let piece = match self.get_piece(from) {
-            Some(p) => p,
-            None => return false,
-        };
+    /// A fresh game on `grid` with `turn` to move and no history.
+    fn with_grid(grid: [[Option<Piece>; WIDTH]; HEIGHT], turn: Color) -> Self {
+        Self {
+            grid,
+            turn,
+            selected: None,
+            state: GameState::Playing,
+            halfmove_clock: 0,
+            fullmove_number: 1,
+            inactivity_limit: DEFAULT_INACTIVITY_LIMIT,
+            history: Vec::new(),
+            draw_offer: None,
+            counting: None,
+            result_reason: None,
+            on_event: EventHandler::default(),
+        }
+    }
 
-        // Cannot capture own piece
-        if let Some(target) = self.get_piece(to) {
-            if target.color == piece.color {
-                return false;
+    /// Builds a custom position from an empty board, e.g. for puzzles and
+    /// endgame studies. Fails if a square is given twice or lies off the
+    /// board.
+    pub fn from_pieces(pieces: &[(Pos, Piece)], turn: Color) -> Result<Board, BoardError> {
+        let mut grid = [[None; WIDTH]; HEIGHT];
+        for &(pos, piece) in pieces {
+            if !pos.in_bounds() {
+                return Err(BoardError::OutOfBounds(pos));
             }
+            if grid[pos.y][pos.x].is_some() {
+                return Err(BoardError::Occupied(pos));
+            }
+            grid[pos.y][pos.x] = Some(piece);
         }
+        Ok(Board::with_grid(grid, turn))
+    }
 
-        let dx = (to.x as i32 - from.x as i32).abs();
-        let dy = (to.y as i32 - from.y as i32).abs();
-
-        match piece.piece_type {
-            PieceType::General => {
-                // Must stay in palace and move 1 step orthogonally
-                if dx + dy != 1 {
-                    return false;
-                }
-                if to.x < 3 || to.x > 5 {
-                    return false;
-                }
-                match piece.color {
-                    Color::Red => {
-                        if to.y < 7 {
-                            return false;
-                        }
-                    }
-                    Color::Black => {
-                        if to.y > 2 {
-                            return false;
-                        }
-                    }
-                }
-                true
+    /// Checks that the position could arise in a real game: one General per
+    /// side inside its palace, Advisors and Elephants on their points, no
+    /// more of any piece than a side starts with, and the side that just
+    /// moved not left in check. Reports every violation found.
+    pub fn validate(&self) -> Result<(), Vec<BoardError>> {
+        let mut errors = Vec::new();
+        for color in [Color::Red, Color::Black] {
+            let material = self.material(color);
+            if material[PieceType::General as usize] == 0 {
+                errors.push(BoardError::MissingGeneral(color));
             }
-            PieceType::Advisor => {
-                // Must stay in palace and move 1 step diagonally
-                if dx != 1 || dy != 1 {
-                    return false;
-                }
-                if to.x < 3 || to.x > 5 {
-                    return false;
+            for piece_type in [
+                PieceType::General,
+                PieceType::Advisor,
+                PieceType::Elephant,
+                PieceType::Horse,
+                PieceType::Chariot,
+                PieceType::Cannon,
+                PieceType::Soldier,
+            ] {
+                let limit = match piece_type {
+                    PieceType::General => 1,
+                    PieceType::Soldier => 5,
+                    _ => 2,
+                };
+                if material[piece_type as usize] > limit {
+                    errors.push(BoardError::TooManyPieces(color, piece_type));
                 }
-                match piece.color {
-                    Color::Red => {
-                        if to.y < 7 {
-                            return false;
-                        }
+            }
+        }
+
+        for y in 0..HEIGHT {
+            for x in 0..WIDTH {
+                let piece = match self.grid[y][x] {
+                    Some(p) => p,
+                    None => continue,
+                };
+                let pos = Pos::new(x, y);
+                match piece.piece_type {
+                    PieceType::General if !in_palace(pos, piece.color) => {
+                        errors.push(BoardError::GeneralOutsidePalace(pos));
                     }
-                    Color::Black => {
-                        if to.y > 2 {
-                            return false;
-                        }
+                    PieceType::Advisor if !is_advisor_point(pos, piece.color) => {
+                        errors.push(BoardError::AdvisorOffSquare(pos));
                     }
-                }
-                true
-            }
-            PieceType::Elephant => {
-                // Move 2 steps diagonally, cannot cross river, eye cannot be blocked
-                if dx != 2 || dy != 2 {
-                    return false;
-                }
-                match piece.color {
-                    Color::Red => {
-                        if to.y < 5 {
-                            return false;
-                        }
+                    PieceType::Elephant if !on_own_side(pos, piece.color) => {
+                        errors.push(BoardError::ElephantAcrossRiver(pos));
                     }
-                    Color::Black => {
-                        if to.y > 4 {
-                            return false;
-                        }
+                    PieceType::Elephant if !is_elephant_point(pos, piece.color) => {
+                        errors.push(BoardError::ElephantOffSquare(pos));
                     }
+                    _ => {}
                 }
-                // Check eye
-                let eye_x = (from.x + to.x) / 2;
-                let eye_y = (from.y + to.y) / 2;
-                if self.grid[eye_y][eye_x].is_some() {
-                    return false;
-                }
-                true
-            }
-            PieceType::Horse => {
-                // Move "L" shape (1 orthogonal + 1 diagonal), check for blocking leg
-                if !((dx == 1 && dy == 2) || (dx == 2 && dy == 1)) {
-                    return false;
-                }
-                // Check leg
-                let leg_x = if dx == 2 { (from.x + to.x) / 2 } else { from.x };
-                let leg_y = if dy == 2 { (from.y + to.y) / 2 } else { from.y };
-                if self.grid[leg_y][leg_x].is_some() {
-                    return false;
-                }
-                true
-            }
-            PieceType::Chariot => {
-                // Move any distance orthogonally, cannot jump
-                if dx != 0 && dy != 0 {
-                    return false;
-                }
-                self.count_obstacles(from, to) == 0
             }
-            PieceType::Cannon => {
-                // Move like Chariot, capture by jumping over exactly one piece
-                if dx != 0 && dy != 0 {
-                    return false;
-                }
-                let obstacles = self.count_obstacles(from, to);
-                if self.get_piece(to).is_some() {
-                    obstacles == 1
+        }
+
+        if self.generals_facing() {
+            errors.push(BoardError::GeneralsFacing);
+        } else if self.is_in_check(self.turn.opposite()) {
+            errors.push(BoardError::OpponentInCheck);
+        }
+
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(errors)
+        }
+    }
+
+    pub fn get_piece(&self, pos: Pos) -> Option<Piece> {
+        if pos.in_bounds() {
+            self.grid[pos.y][pos.x]
+        } else {
+            None
+        }
+    }
+
+    /// Full FEN: piece placement, side to move, the unused castling and
+    /// en-passant fields, then the halfmove clock and fullmove number, e.g.
+    /// `rnbakabnr/9/1c5c1/p1p1p1p1p/9/9/P1P1P1P1P/1C5C1/9/RNBAKABNR w - - 0 1`.
+    pub fn to_fen(&self) -> String {
+        format!(
+            "{} - - {} {}",
+            self.to_fen_short(),
+            self.halfmove_clock,
+            self.fullmove_number
+        )
+    }
+
+    /// Parses a full or short FEN as produced by [`Board::to_fen`] and
+    /// [`Board::to_fen_short`]. Missing move counters default to `0 1`. A
+    /// position where the side to move has no legal move is loaded as won
+    /// for the other side.
+    pub fn from_fen(fen: &str) -> Result<Board, FenError> {
+        let mut fields = fen.split_whitespace();
+        let placement = fields.next().unwrap_or("");
+        let ranks: Vec<&str> = placement.split('/').collect();
+        if ranks.len() != HEIGHT {
+            return Err(FenError::RankCount(ranks.len()));
+        }
+
+        let mut grid = [[None; WIDTH]; HEIGHT];
+        for (y, rank) in ranks.iter().enumerate() {
+            let mut x = 0;
+            for c in rank.chars() {
+                if let Some(skip) = c.to_digit(10) {
+                    x += skip as usize;
                 } else {
-                    obstacles == 0
-                }
-            }
-            PieceType::Soldier => {
-                // Move 1 step forward. After crossing river, can also move sideways.
-                if dx + dy != 1 {
-                    return false;
-                }
-                match piece.color {
-                    Color::Red => {
-                        if (to.y as i32) > (from.y as i32) {
-                            return false;
-                        } // Cannot move back
-                        if from.y >= 5 && dx != 0 {
-                            return false;
-                        } // Before river, only forward
-                    }
-                    Color::Black => {
-                        if (to.y as i32) < (from.y as i32) {
-                            return false;
-                        } // Cannot move back
-                        if from.y <= 4 && dx != 0 {
-                            return false;
-                        } // Before river, only forward
+                    let piece = Piece::from_fen_char(c).ok_or(FenError::UnknownPiece(c))?;
+                    if x >= WIDTH {
+                        return Err(FenError::RankWidth(y));
                     }
+                    grid[y][x] = Some(piece);
+                    x += 1;
                 }
-                true
             }
+            if x != WIDTH {
+                return Err(FenError::RankWidth(y));
+            }
+        }
+
+        let turn = match fields.next() {
+            Some("w") | Some("r") => Color::Red,
+            Some("b") => Color::Black,
+            Some(other) => return Err(FenError::InvalidSideToMove(other.to_string())),
+            None => return Err(FenError::MissingSideToMove),
+        };
+
+        // Castling and en-passant fields are always "-" in Xiangqi
+        let mut counters = fields.skip(2);
+        let mut counter = |default: u32| match counters.next() {
+            Some(text) => text
+                .parse::<u32>()
+                .map_err(|_| FenError::InvalidCounter(text.to_string())),
+            None => Ok(default),
+        };
+        let halfmove_clock = counter(0)?;
+        let fullmove_number = counter(1)?;
+
+        let mut board = Board {
+            halfmove_clock,
+            fullmove_number,
+            ..Board::with_grid(grid, turn)
+        };
+        board.validate().map_err(FenError::Invalid)?;
+        if board.all_legal_moves().is_empty() {
+            board.lose_without_moves();
         }
+        Ok(board)
     }
 
-    fn count_obstacles(&self, from: Pos, to: Pos) -> i32 {
-        let mut count = 0;
-        if from.x == to.x {
-            let (min_y, max_y) = if from.y < to.y {
-                (from.y, to.y)
-            } else {
-                (to.y, from.y)
-            };
-            for y in (min_y + 1)..max_y {
-                if self.grid[y][from.x].is_some() {
-                    count += 1;
-                }
+    /// Minimal FEN with only piece placement and side to move, for parsers
+    /// that reject the trailing fields.
+    pub fn to_fen_short(&self) -> String {
+        let mut fen = String::new();
+        for (y, row) in self.grid.iter().enumerate() {
+            if y > 0 {
+                fen.push('/');
             }
-        } else {
-            let (min_x, max_x) = if from.x < to.x {
-                (from.x, to.x)
-            } else {
-                (to.x, from.x)
-            };
-            for x in (min_x + 1)..max_x {
-                if self.grid[from.y][x].is_some() {
-                    count += 1;
+            let mut empty = 0;
+            for cell in row {
+                match cell {
+                    Some(piece) => {
+                        if empty > 0 {
+                            fen.push_str(&empty.to_string());
+                            empty = 0;
+                        }
+                        fen.push(piece.fen_char());
+                    }
+                    None => empty += 1,
                 }
             }
+            if empty > 0 {
+                fen.push_str(&empty.to_string());
+            }
         }
-        count
+        fen.push(' ');
+        fen.push(match self.turn {
+            Color::Red => 'w',
+            Color::Black => 'b',
+        });
+        fen
+    }
+
+    pub fn move_piece(&mut self, from: Pos, to: Pos) -> bool {
+        self.try_move(from, to).is_ok()
+    }
+
+    /// Like [`Board::move_piece`], but reports why a move was refused.
+    pub fn try_move(&mut self, from: Pos, to: Pos) -> Result<(), MoveError> {
+        self.explain_move(from, to)?;
+
+        let captured = self.get_piece(to);
+        self.grid[to.y][to.x] = self.grid[from.y][from.x];
+        self.grid[from.y][from.x] = None;
+        if self.draw_offer == Some(self.turn.opposite()) {
+            self.draw_offer = None;
+        }
+        self.history.push(HistoryEntry {
+            from,
+            to,
+            captured,
+            halfmove_clock: self.halfmove_clock,
+        });
+
+        if captured.is_some() {
+            self.halfmove_clock = 0;
+        } else {
+            self.halfmove_clock += 1;
+        }
+        if self.turn == Color::Black {
+            self.fullmove_number += 1;
+        }
+
+        if self.state == GameState::Playing {
+            self.turn = self.turn.opposite();
+            // Checkmate or stalemate: a side with no legal move loses
+            if self.all_legal_moves().is_empty() {
+                self.lose_without_moves();
+            } else if (captured.is_some() && self.is_insufficient_material())
+                || self.is_draw_by_counting()
+            {
+                self.state = GameState::Draw;
+            }
+        }
+
+        self.emit(Event::Moved { from, to });
+        if let Some(piece) = captured {
+            self.emit(Event::Captured(piece));
+        }
+        if self.is_in_check(self.turn) {
+            self.emit(Event::Check(self.turn));
+        }
+        if self.state != GameState::Playing {
+            self.emit(Event::GameOver(self.state));
+        }
+        Ok(())
+    }
+
+    /// Plays `moves` in order. On the first one refused, gives its index and
+    /// why, and puts the board back the way it was before the call. Events
+    /// for the moves already played have been sent by then.
+    pub fn apply_moves(&mut self, moves: &[(Pos, Pos)]) -> Result<(), (usize, MoveError)> {
+        let snapshot = self.snapshot();
+        let selected = self.selected;
+        for (index, &(from, to)) in moves.iter().enumerate() {
+            if let Err(err) = self.try_move(from, to) {
+                self.restore(snapshot);
+                self.selected = selected;
+                return Err((index, err));
+            }
+        }
+        Ok(())
+    }
+
+    /// Checks a move for the side to move without playing it, returning
+    /// the first rule it breaks: the game being over, then whose piece it
+    /// is, then how the piece moves, then check.
+    pub fn explain_move(&self, from: Pos, to: Pos) -> Result<(), MoveError> {
+        if self.state != GameState::Playing {
+            return Err(MoveError::GameOver);
+        }
+        let piece = self.get_piece(from).ok_or(MoveError::NoPiece(from))?;
+        if piece.color != self.turn {
+            return Err(MoveError::NotYourTurn(from));
+        }
+        self.check_movement(from, to)?;
+        if is_general(self.get_piece(to)) {
+            return Err(MoveError::CapturesGeneral);
+        }
+        if !self.is_legal_move(from, to) {
+            return Err(MoveError::LeavesGeneralInCheck);
+        }
+        Ok(())
+    }
+
+    /// Takes back the last move, returning false when there is none.
+    pub fn undo(&mut self) -> bool {
+        let entry = match self.history.pop() {
+            Some(entry) => entry,
+            None => return false,
+        };
+        let mover = match self.grid[entry.to.y][entry.to.x] {
+            Some(piece) => piece.color,
+            None => return false,
+        };
+        self.grid[entry.from.y][entry.from.x] = self.grid[entry.to.y][entry.to.x];
+        self.grid[entry.to.y][entry.to.x] = entry.captured;
+        self.turn = mover;
+        self.state = GameState::Playing;
+        self.result_reason = None;
+        self.draw_offer = None;
+        self.halfmove_clock = entry.halfmove_clock;
+        if mover == Color::Black {
+            self.fullmove_number -= 1;
+        }
+        self.selected = None;
+        true
+    }
+
+    /// A copy of the current position without the game record, for
+    /// analysis where copying a long history would be wasted work. The
+    /// copy can't be undone past this point.
+    pub fn position_only(&self) -> Board {
+        Board {
+            grid: self.grid,
+            turn: self.turn,
+            selected: self.selected,
+            state: self.state,
+            halfmove_clock: self.halfmove_clock,
+            fullmove_number: self.fullmove_number,
+            inactivity_limit: self.inactivity_limit,
+            history: Vec::new(),
+            draw_offer: self.draw_offer,
+            counting: self.counting,
+            result_reason: self.result_reason,
+            on_event: EventHandler::default(),
+        }
+    }
+
+    /// The game reflected across the centre file, `x` becoming `8 - x`, with
+    /// colours, turn and counters unchanged. The history is reflected too,
+    /// so mirroring twice gives back the same game.
+    pub fn mirror_horizontal(&self) -> Board {
+        let mirror = |pos: Pos| Pos::new(WIDTH - 1 - pos.x, pos.y);
+        let mut board = self.clone();
+        for row in &mut board.grid {
+            row.reverse();
+        }
+        board.selected = self.selected.map(mirror);
+        for entry in &mut board.history {
+            entry.from = mirror(entry.from);
+            entry.to = mirror(entry.to);
+        }
+        board
+    }
+
+    pub fn snapshot(&self) -> BoardSnapshot {
+        BoardSnapshot {
+            grid: self.grid,
+            turn: self.turn,
+            state: self.state,
+            halfmove_clock: self.halfmove_clock,
+            fullmove_number: self.fullmove_number,
+            inactivity_limit: self.inactivity_limit,
+            history: self.history.clone(),
+            draw_offer: self.draw_offer,
+            counting: self.counting,
+            result_reason: self.result_reason,
+        }
+    }
+
+    /// Puts the game back the way it was when `snapshot` was taken and
+    /// clears the selection. The event handler stays.
+    pub fn restore(&mut self, snapshot: BoardSnapshot) {
+        *self = Board {
+            grid: snapshot.grid,
+            turn: snapshot.turn,
+            selected: None,
+            state: snapshot.state,
+            halfmove_clock: snapshot.halfmove_clock,
+            fullmove_number: snapshot.fullmove_number,
+            inactivity_limit: snapshot.inactivity_limit,
+            history: snapshot.history,
+            draw_offer: snapshot.draw_offer,
+            counting: snapshot.counting,
+            result_reason: snapshot.result_reason,
+            on_event: std::mem::take(&mut self.on_event),
+        };
+    }
+
+    /// The moves played so far, oldest first.
+    pub fn move_log(&self) -> Vec<(Pos, Pos)> {
+        self.history
+            .iter()
+            .map(|entry| (entry.from, entry.to))
+            .collect()
+    }
+
+    /// Every position of the game so far, from the starting position up to
+    /// and including the current one.
+    pub fn positions(&self) -> Vec<Board> {
+        let mut board = self.clone();
+        let mut positions = vec![board.clone()];
+        while board.undo() {
+            positions.push(board.clone());
+        }
+        positions.reverse();
+        positions
+    }
+
+    /// Calls `handler` with every [`Event`] of the game from now on: moves
+    /// played through [`Board::try_move`], and the game ending by a move,
+    /// resignation or agreed draw. Replaces any earlier handler.
+    pub fn set_event_handler(&mut self, handler: impl FnMut(Event) + Send + 'static) {
+        self.on_event = EventHandler(Some(Box::new(handler)));
+    }
+
+    fn emit(&mut self, event: Event) {
+        if let Some(handler) = &mut self.on_event.0 {
+            handler(event);
+        }
+    }
+
+    /// `who` resigns, handing the game to the opponent.
+    pub fn resign(&mut self, who: Color) {
+        self.lose(who, WinReason::Resignation);
+    }
+
+    /// `who` has run out of time, handing the game to the opponent.
+    pub fn lose_on_time(&mut self, who: Color) {
+        self.lose(who, WinReason::Timeout);
+    }
+
+    fn lose(&mut self, who: Color, reason: WinReason) {
+        if self.state == GameState::Playing {
+            self.state = GameState::Won(who.opposite());
+            self.result_reason = Some(reason);
+            self.draw_offer = None;
+            self.emit(Event::GameOver(self.state));
+        }
+    }
+
+    /// Ends the game as lost for the side to move, which has no legal move,
+    /// by checkmate or stalemate. The caller reports the result.
+    pub(crate) fn lose_without_moves(&mut self) {
+        self.result_reason = Some(if self.is_in_check(self.turn) {
+            WinReason::Checkmate
+        } else {
+            WinReason::Stalemate
+        });
+        self.state = GameState::Won(self.turn.opposite());
+    }
+
+    /// Records a draw offer from `who`, to be answered by the opponent with
+    /// [`Board::accept_draw`] or [`Board::decline_draw`]. Returns false once
+    /// the game is over.
+    pub fn offer_draw(&mut self, who: Color) -> bool {
+        if self.state != GameState::Playing {
+            return false;
+        }
+        self.draw_offer = Some(who);
+        true
+    }
+
+    /// `who` accepts the opponent's pending draw offer, ending the game.
+    /// Returns false when there is no such offer.
+    pub fn accept_draw(&mut self, who: Color) -> bool {
+        if self.state != GameState::Playing || self.draw_offer != Some(who.opposite()) {
+            return false;
+        }
+        self.state = GameState::Draw;
+        self.draw_offer = None;
+        self.emit(Event::GameOver(self.state));
+        true
+    }
+
+    pub fn decline_draw(&mut self) {
+        self.draw_offer = None;
+    }
+
+    /// Whether `inactivity_limit` half-moves have passed without a capture.
+    pub fn is_draw_by_inactivity(&self) -> bool {
+        self.halfmove_clock >= self.inactivity_limit
+    }
+
+    /// Moves the attacking side has made under the counting rule, or `None`
+    /// when the rule is off or not yet running: there is still too much
+    /// material, or neither side has more attacking pieces than the other.
+    pub fn counting_status(&self) -> Option<u32> {
+        let rules = self.counting?;
+        let attackers = |color| {
+            let counts = self.material(color);
+            [
+                PieceType::Chariot,
+                PieceType::Horse,
+                PieceType::Cannon,
+                PieceType::Soldier,
+            ]
+            .iter()
+            .map(|&piece_type| counts[piece_type as usize])
+            .sum::<u32>()
+        };
+        let (red, black) = (attackers(Color::Red), attackers(Color::Black));
+        if red + black > rules.material_threshold || red == black {
+            return None;
+        }
+        let attacker = if red > black {
+            Color::Red
+        } else {
+            Color::Black
+        };
+        // Half-moves since the last capture alternate sides, the latest
+        // being by the side not to move
+        let moves = if attacker == self.turn {
+            self.halfmove_clock / 2
+        } else {
+            self.halfmove_clock.div_ceil(2)
+        };
+        Some(moves)
+    }
+
+    /// Whether the attacking side has used up its moves under the counting
+    /// rule, which draws the game.
+    pub fn is_draw_by_counting(&self) -> bool {
+        self.counting
+            .zip(self.counting_status())
+            .is_some_and(|(rules, count)| count >= rules.move_limit)
+    }
+
+    /// Judges the repetition once the current position has occurred
+    /// [`REPETITION_COUNT`] times, looking at the moves played since its
+    /// first occurrence. A side that checked with every move is judged
+    /// first; if both did, neither is to blame. `None` while the position
+    /// hasn't repeated often enough.
+    ///
+    /// A chase is a move after which the moved piece attacks an undefended
+    /// Chariot, Horse, Cannon, Advisor or Elephant. Generals and Soldiers
+    /// chase freely and Soldiers may be chased, as in the usual rules; the
+    /// finer points such as mutual chases are not distinguished.
+    pub fn repetition_kind(&self) -> Option<RepetitionKind> {
+        let target = self.zobrist_hash();
+        let mut board = self.clone();
+        let mut seen = 1;
+        // Per side: moves in the cycle, checks among them, and moves that
+        // were checks or chases
+        let mut moves = [0; 2];
+        let mut checks = [0; 2];
+        let mut forcing = [0; 2];
+        while seen < REPETITION_COUNT
+            && let Some(entry) = board.history.last().copied()
+        {
+            let mover = board.turn.opposite() as usize;
+            moves[mover] += 1;
+            if board.is_in_check(board.turn) {
+                checks[mover] += 1;
+                forcing[mover] += 1;
+            } else if board.is_chasing(entry.to) {
+                forcing[mover] += 1;
+            }
+            board.undo();
+            // Nothing before a capture can come back
+            if entry.captured.is_some() {
+                return None;
+            }
+            if board.zobrist_hash() == target {
+                seen += 1;
+            }
+        }
+        if seen < REPETITION_COUNT {
+            return None;
+        }
+
+        let always = |counts: [u32; 2], color: Color| {
+            moves[color as usize] > 0 && counts[color as usize] == moves[color as usize]
+        };
+        let one_sided =
+            |counts: [u32; 2]| match (always(counts, Color::Red), always(counts, Color::Black)) {
+                (true, false) => Some(Color::Red),
+                (false, true) => Some(Color::Black),
+                _ => None,
+            };
+        if always(checks, Color::Red) && always(checks, Color::Black) {
+            return Some(RepetitionKind::NeutralDraw);
+        }
+        if let Some(checker) = one_sided(checks) {
+            return Some(RepetitionKind::PerpetualCheck(checker));
+        }
+        Some(match one_sided(forcing) {
+            Some(chaser) => RepetitionKind::PerpetualChase(chaser),
+            None => RepetitionKind::NeutralDraw,
+        })
+    }
+
+    /// Whether the piece on `from` could capture an enemy piece that has
+    /// no defender, as judged by [`Board::repetition_kind`].
+    fn is_chasing(&self, from: Pos) -> bool {
+        let Some(chaser) = self.get_piece(from) else {
+            return false;
+        };
+        if matches!(chaser.piece_type, PieceType::General | PieceType::Soldier) {
+            return false;
+        }
+        (0..HEIGHT).any(|y| {
+            (0..WIDTH).any(|x| {
+                let square = Pos::new(x, y);
+                self.get_piece(square).is_some_and(|target| {
+                    target.color != chaser.color
+                        && !matches!(target.piece_type, PieceType::General | PieceType::Soldier)
+                        && self.can_move(from, square)
+                        && self.attack_count(square, target.color) == 0
+                })
+            })
+        })
+    }
+
+    /// Whether neither side has a Chariot, Horse, Cannon or Soldier left.
+    /// Generals, Advisors and Elephants can't give mate, so
+    /// [`Board::try_move`] declares a draw once a capture leaves only them.
+    pub fn is_insufficient_material(&self) -> bool {
+        self.grid.iter().flatten().flatten().all(|piece| {
+            matches!(
+                piece.piece_type,
+                PieceType::General | PieceType::Advisor | PieceType::Elephant
+            )
+        })
+    }
+
+    /// Number of pieces of `color` on the board, indexed by `PieceType as usize`.
+    pub fn material(&self, color: Color) -> [u32; 7] {
+        let mut counts = [0; 7];
+        for piece in self.grid.iter().flatten().flatten() {
+            if piece.color == color {
+                counts[piece.piece_type as usize] += 1;
+            }
+        }
+        counts
+    }
+
+    /// Conservative check for material balances that are textbook draws,
+    /// used to stop playing on and to offer adjudication. It is deliberately
+    /// narrow and only fires when at most one side has attacking material
+    /// (Chariot, Horse, Cannon or Soldier), in one of these cases:
+    ///
+    /// - neither side has any attacking material;
+    /// - a lone Chariot against two Advisors and two Elephants;
+    /// - a lone Horse against two Advisors;
+    /// - a lone Cannon, with no Advisor or Elephant of its own to use as a
+    ///   screen, against a bare General.
+    ///
+    /// Soldiers are never treated as drawn, since whether they win depends
+    /// on how far they have advanced.
+    pub fn is_likely_fortress(&self) -> bool {
+        let red = self.material(Color::Red);
+        let black = self.material(Color::Black);
+        let attackers = |m: &[u32; 7]| {
+            [
+                PieceType::Chariot,
+                PieceType::Horse,
+                PieceType::Cannon,
+                PieceType::Soldier,
+            ]
+            .iter()
+            .map(|&pt| m[pt as usize])
+            .sum::<u32>()
+        };
+        let (attacker, defender) = match (attackers(&red), attackers(&black)) {
+            (0, 0) => return true,
+            (_, 0) => (red, black),
+            (0, _) => (black, red),
+            _ => return false,
+        };
+        if attackers(&attacker) != 1 {
+            return false;
+        }
+        let advisors = defender[PieceType::Advisor as usize];
+        let elephants = defender[PieceType::Elephant as usize];
+        if attacker[PieceType::Chariot as usize] == 1 {
+            advisors == 2 && elephants == 2
+        } else if attacker[PieceType::Horse as usize] == 1 {
+            advisors == 2
+        } else if attacker[PieceType::Cannon as usize] == 1 {
+            attacker[PieceType::Advisor as usize] == 0
+                && attacker[PieceType::Elephant as usize] == 0
+                && advisors == 0
+                && elephants == 0
+        } else {
+            false
+        }
+    }
+
+    /// Position of `color`'s General, if it is on the board.
+    pub fn find_general(&self, color: Color) -> Option<Pos> {
+        for y in 0..HEIGHT {
+            for x in 0..WIDTH {
+                if let Some(piece) = self.grid[y][x]
+                    && piece.color == color
+                    && piece.piece_type == PieceType::General
+                {
+                    return Some(Pos::new(x, y));
+                }
+            }
+        }
+        None
+    }
+
+    /// Whether `color`'s General is attacked, counting the "flying general"
+    /// rule: the two Generals may not face each other on an open file.
+    pub fn is_in_check(&self, color: Color) -> bool {
+        let general = match self.find_general(color) {
+            Some(p) => p,
+            None => return false,
+        };
+        if self.generals_facing() {
+            return true;
+        }
+        for y in 0..HEIGHT {
+            for x in 0..WIDTH {
+                match self.grid[y][x] {
+                    // The palaces are too far apart for Generals to attack
+                    // each other except by facing
+                    Some(p) if p.color != color && p.piece_type != PieceType::General => {}
+                    _ => continue,
+                }
+                if self.is_valid_move(Pos::new(x, y), general) {
+                    return true;
+                }
+            }
+        }
+        false
+    }
+
+    /// Whether the Generals stand on the same file with nothing between
+    /// them. Any move leading to this is illegal, since the side that moved
+    /// would be in check from the opposing General.
+    pub fn generals_facing(&self) -> bool {
+        match (
+            self.find_general(Color::Red),
+            self.find_general(Color::Black),
+        ) {
+            (Some(red), Some(black)) => red.x == black.x && self.count_obstacles(red, black) == 0,
+            _ => false,
+        }
+    }
+
+    /// Pieces of `color` pinned to their General: pieces whose removal would
+    /// expose the General to a Chariot, Cannon or the opposing General.
+    /// Returns nothing while `color` is already in check.
+    pub fn pinned_pieces(&self, color: Color) -> Vec<Pos> {
+        if self.is_in_check(color) {
+            return Vec::new();
+        }
+        let mut pinned = Vec::new();
+        for y in 0..HEIGHT {
+            for x in 0..WIDTH {
+                match self.grid[y][x] {
+                    Some(p) if p.color == color && p.piece_type != PieceType::General => {}
+                    _ => continue,
+                }
+                let mut without = Board::with_grid(self.grid, self.turn);
+                without.grid[y][x] = None;
+                if without.is_in_check(color) {
+                    pinned.push(Pos::new(x, y));
+                }
+            }
+        }
+        pinned
+    }
+
+    /// Legal destinations for the piece at `from`, whoever's turn it is.
+    /// Moves that would leave the mover's own General in check or take the
+    /// enemy General are excluded.
+    pub fn legal_moves_from(&self, from: Pos) -> Vec<Pos> {
+        let piece = match self.get_piece(from) {
+            Some(p) => p,
+            None => return Vec::new(),
+        };
+        move_candidates(from, piece.piece_type)
+            .into_iter()
+            .filter(|&to| !is_general(self.get_piece(to)) && self.is_legal_move(from, to))
+            .collect()
+    }
+
+    /// Whether the piece at `from` may move to `to`, whoever's turn it is
+    /// and whether or not the game is over. Moves that would leave the
+    /// mover's own General in check are rejected; [`Board::try_move`] adds
+    /// the turn and game state checks on top, and refuses to take a
+    /// General. Attacks on a General therefore count here.
+    pub fn can_move(&self, from: Pos, to: Pos) -> bool {
+        self.is_legal_move(from, to)
+    }
+
+    /// Number of `by`'s pieces that could legally capture on `square`,
+    /// whatever stands there now. The square is treated as holding an
+    /// enemy piece, so Cannons count only with a screen and a piece of
+    /// `by`'s own on the square counts its defenders.
+    pub fn attack_count(&self, square: Pos, by: Color) -> u32 {
+        if !square.in_bounds() {
+            return 0;
+        }
+        let mut board = Board::with_grid(self.grid, self.turn);
+        let piece_type = self
+            .get_piece(square)
+            .map_or(PieceType::Soldier, |piece| piece.piece_type);
+        board.grid[square.y][square.x] = Some(Piece {
+            color: by.opposite(),
+            piece_type,
+        });
+        let mut count = 0;
+        for y in 0..HEIGHT {
+            for x in 0..WIDTH {
+                let from = Pos::new(x, y);
+                if let Some(piece) = board.grid[y][x]
+                    && piece.color == by
+                    && board.can_move(from, square)
+                {
+                    count += 1;
+                }
+            }
+        }
+        count
+    }
+
+    /// Number of moves `color`'s pieces could make by their movement rules
+    /// alone, without checking whether the General is left in check. Much
+    /// cheaper than counting legal moves, for use in evaluation.
+    pub fn mobility(&self, color: Color) -> u32 {
+        let mut count = 0;
+        for y in 0..HEIGHT {
+            for x in 0..WIDTH {
+                if let Some(piece) = self.grid[y][x]
+                    && piece.color == color
+                {
+                    let from = Pos::new(x, y);
+                    count += move_candidates(from, piece.piece_type)
+                        .into_iter()
+                        .filter(|&to| self.is_valid_move(from, to))
+                        .count() as u32;
+                }
+            }
+        }
+        count
+    }
+
+    /// Every legal `(from, to)` move for the side to move. The AI, mate
+    /// detection and [`Board::perft`] all generate moves through this, so
+    /// they agree on what is legal.
+    pub fn all_legal_moves(&self) -> Vec<(Pos, Pos)> {
+        let mut moves = Vec::new();
+        for y in 0..HEIGHT {
+            for x in 0..WIDTH {
+                if let Some(piece) = self.grid[y][x]
+                    && piece.color == self.turn
+                {
+                    let from = Pos::new(x, y);
+                    moves.extend(self.legal_moves_from(from).into_iter().map(|to| (from, to)));
+                }
+            }
+        }
+        moves
+    }
+
+    /// Number of move sequences `depth` plies deep from this position, for
+    /// checking move generation against known counts (44, 1920, 79666 from
+    /// the start).
+    pub fn perft(&self, depth: u32) -> u64 {
+        if depth == 0 {
+            return 1;
+        }
+        let moves = self.all_legal_moves();
+        if depth == 1 {
+            return moves.len() as u64;
+        }
+        moves
+            .into_iter()
+            .map(|(from, to)| {
+                let mut next = Board::with_grid(self.grid, self.turn);
+                next.play_unchecked(from, to);
+                next.perft(depth - 1)
+            })
+            .sum()
+    }
+
+    /// Legal moves for `color` grouped by the type of the moving piece, in
+    /// board scan order within each group. Types with no legal move are
+    /// left out.
+    pub fn legal_moves_by_type(&self, color: Color) -> HashMap<PieceType, Vec<(Pos, Pos)>> {
+        let mut groups: HashMap<PieceType, Vec<(Pos, Pos)>> = HashMap::new();
+        for y in 0..HEIGHT {
+            for x in 0..WIDTH {
+                if let Some(piece) = self.grid[y][x]
+                    && piece.color == color
+                {
+                    let from = Pos::new(x, y);
+                    let moves = self.legal_moves_from(from);
+                    if !moves.is_empty() {
+                        groups
+                            .entry(piece.piece_type)
+                            .or_default()
+                            .extend(moves.into_iter().map(|to| (from, to)));
+                    }
+                }
+            }
+        }
+        groups
+    }
+
+    /// When the side to move is in check and only one of its pieces has any
+    /// legal move, returns that piece's square.
+    pub fn sole_defender(&self) -> Option<Pos> {
+        if !self.is_in_check(self.turn) {
+            return None;
+        }
+        let mut origins = self.all_legal_moves().into_iter().map(|(from, _)| from);
+        let first = origins.next()?;
+        if origins.all(|from| from == first) {
+            Some(first)
+        } else {
+            None
+        }
+    }
+
+    /// Moves a piece and passes the turn without validation or bookkeeping.
+    /// For search on scratch copies, with moves from `all_legal_moves`.
+    pub(crate) fn play_unchecked(&mut self, from: Pos, to: Pos) {
+        self.grid[to.y][to.x] = self.grid[from.y][from.x];
+        self.grid[from.y][from.x] = None;
+        self.turn = self.turn.opposite();
+    }
+
+    /// Piece movement rules plus the requirement that the mover's own
+    /// General is not left in check.
+    fn is_legal_move(&self, from: Pos, to: Pos) -> bool {
+        if !self.is_valid_move(from, to) {
+            return false;
+        }
+        let color = self.grid[from.y][from.x].unwrap().color;
+        let mut next = Board::with_grid(self.grid, self.turn);
+        next.grid[to.y][to.x] = next.grid[from.y][from.x];
+        next.grid[from.y][from.x] = None;
+        !next.is_in_check(color)
+    }
+
+    fn is_valid_move(&self, from: Pos, to: Pos) -> bool {
+        self.check_movement(from, to).is_ok()
+    }
+
+    /// The piece movement rules behind [`Board::is_valid_move`], saying
+    /// which rule a move breaks. Whose turn it is and check are ignored.
+    fn check_movement(&self, from: Pos, to: Pos) -> Result<(), MoveError> {
+        if from == to || !to.in_bounds() {
+            return Err(MoveError::InvalidMove);
+        }
+
+        let piece = self.get_piece(from).ok_or(MoveError::NoPiece(from))?;
+
+        // Cannot capture own piece
+        if let Some(target) = self.get_piece(to)
+            && target.color == piece.color
+        {
+            return Err(MoveError::CapturesOwnPiece(to));
+        }
+
+        let dx = (to.x as i32 - from.x as i32).abs();
+        let dy = (to.y as i32 - from.y as i32).abs();
+
+        match piece.piece_type {
+            PieceType::General => {
+                // Must stay in palace and move 1 step orthogonally
+                if dx + dy != 1 {
+                    return Err(MoveError::InvalidMove);
+                }
+                if !in_palace(to, piece.color) {
+                    return Err(MoveError::LeavesPalace);
+                }
+                Ok(())
+            }
+            PieceType::Advisor => {
+                // Must stay in palace and move 1 step diagonally
+                if dx != 1 || dy != 1 {
+                    return Err(MoveError::InvalidMove);
+                }
+                if !in_palace(to, piece.color) {
+                    return Err(MoveError::LeavesPalace);
+                }
+                Ok(())
+            }
+            PieceType::Elephant => {
+                // Move 2 steps diagonally, cannot cross river, eye cannot be blocked
+                if dx != 2 || dy != 2 {
+                    return Err(MoveError::InvalidMove);
+                }
+                if !on_own_side(to, piece.color) {
+                    return Err(MoveError::CrossesRiver);
+                }
+                // Check eye, which lies between two squares already known
+                // to be on the board and so is on it too
+                let eye = Pos::new((from.x + to.x) / 2, (from.y + to.y) / 2);
+                if self.get_piece(eye).is_some() {
+                    return Err(MoveError::ElephantEyeBlocked { at: eye });
+                }
+                Ok(())
+            }
+            PieceType::Horse => {
+                // Move "L" shape (1 orthogonal + 1 diagonal), check for blocking leg
+                if !((dx == 1 && dy == 2) || (dx == 2 && dy == 1)) {
+                    return Err(MoveError::InvalidMove);
+                }
+                // Check leg
+                let leg_x = if dx == 2 { (from.x + to.x) / 2 } else { from.x };
+                let leg_y = if dy == 2 { (from.y + to.y) / 2 } else { from.y };
+                let leg = Pos::new(leg_x, leg_y);
+                if self.get_piece(leg).is_some() {
+                    return Err(MoveError::HorseLegBlocked { at: leg });
+                }
+                Ok(())
+            }
+            PieceType::Chariot => {
+                // Move any distance orthogonally, cannot jump
+                if dx != 0 && dy != 0 {
+                    return Err(MoveError::InvalidMove);
+                }
+                match self.obstacles(from, to).next() {
+                    Some(at) => Err(MoveError::BlockedPath { at }),
+                    None => Ok(()),
+                }
+            }
+            PieceType::Cannon => {
+                // Move like Chariot, capture by jumping over exactly one piece
+                if dx != 0 && dy != 0 {
+                    return Err(MoveError::InvalidMove);
+                }
+                // A capture needs exactly one screen, so an adjacent enemy
+                // (nothing in between) can't be taken, and a quiet slide
+                // can't pass over anything. Own pieces were rejected above.
+                let mut obstacles = self.obstacles(from, to);
+                if self.get_piece(to).is_some() {
+                    if obstacles.next().is_none() {
+                        return Err(MoveError::NoScreen);
+                    }
+                    // Anything past the screen blocks the shot
+                    match obstacles.next() {
+                        Some(at) => Err(MoveError::BlockedPath { at }),
+                        None => Ok(()),
+                    }
+                } else {
+                    match obstacles.next() {
+                        Some(at) => Err(MoveError::BlockedPath { at }),
+                        None => Ok(()),
+                    }
+                }
+            }
+            PieceType::Soldier => {
+                // Move 1 step forward. After crossing river, can also move sideways.
+                if dx + dy != 1 {
+                    return Err(MoveError::InvalidMove);
+                }
+                // Never backwards. A Soldier on the far rank (row 0 for Red,
+                // row 9 for Black) has no square ahead, so only its
+                // sideways steps are left.
+                let backwards = match piece.color {
+                    Color::Red => to.y > from.y,
+                    Color::Black => to.y < from.y,
+                };
+                if backwards {
+                    return Err(MoveError::InvalidMove);
+                }
+                // Row 5 is still Red's side of the river (row 4 for Black):
+                // a soldier there has reached the bank but not crossed it.
+                if on_own_side(from, piece.color) && dx != 0 {
+                    return Err(MoveError::InvalidMove);
+                }
+                Ok(())
+            }
+        }
+    }
+
+    /// Occupied squares strictly between `from` and `to`, which share a
+    /// rank or file, nearest to `from` first.
+    fn obstacles(&self, from: Pos, to: Pos) -> impl Iterator<Item = Pos> + '_ {
+        let steps = from.x.abs_diff(to.x).max(from.y.abs_diff(to.y));
+        let step = |a: usize, b: usize, i: usize| match a.cmp(&b) {
+            std::cmp::Ordering::Less => a + i,
+            std::cmp::Ordering::Equal => a,
+            std::cmp::Ordering::Greater => a - i,
+        };
+        (1..steps)
+            .map(move |i| Pos::new(step(from.x, to.x, i), step(from.y, to.y, i)))
+            .filter(|&pos| self.get_piece(pos).is_some())
+    }
+
+    /// Pieces strictly between `from` and `to`, which share a rank or
+    /// file; zero for adjacent squares.
+    fn count_obstacles(&self, from: Pos, to: Pos) -> i32 {
+        self.obstacles(from, to).count() as i32
+    }
+}
+
+fn is_general(piece: Option<Piece>) -> bool {
+    piece.is_some_and(|piece| piece.piece_type == PieceType::General)
+}
+
+/// Squares a `piece_type` at `from` might move to: on the board, but
+/// before any movement rule is checked.
+fn move_candidates(from: Pos, piece_type: PieceType) -> Vec<Pos> {
+    let (fx, fy) = (from.x as i32, from.y as i32);
+    let offsets: &[(i32, i32)] = match piece_type {
+        PieceType::General | PieceType::Soldier => &[(1, 0), (-1, 0), (0, 1), (0, -1)],
+        PieceType::Advisor => &[(1, 1), (1, -1), (-1, 1), (-1, -1)],
+        PieceType::Elephant => &[(2, 2), (2, -2), (-2, 2), (-2, -2)],
+        PieceType::Horse => &[
+            (1, 2),
+            (1, -2),
+            (-1, 2),
+            (-1, -2),
+            (2, 1),
+            (2, -1),
+            (-2, 1),
+            (-2, -1),
+        ],
+        PieceType::Chariot | PieceType::Cannon => &[],
+    };
+    let mut candidates: Vec<Pos> = offsets
+        .iter()
+        .filter_map(|(dx, dy)| Pos::try_new(fx + dx, fy + dy))
+        .collect();
+    if matches!(piece_type, PieceType::Chariot | PieceType::Cannon) {
+        candidates.extend((0..WIDTH).map(|x| Pos::new(x, from.y)));
+        candidates.extend((0..HEIGHT).map(|y| Pos::new(from.x, y)));
+    }
+    candidates
+}
+
+fn on_own_side(pos: Pos, color: Color) -> bool {
+    match color {
+        Color::Red => pos.y >= 5,
+        Color::Black => pos.y <= 4,
+    }
+}
+
+fn in_palace(pos: Pos, color: Color) -> bool {
+    (3..=5).contains(&pos.x)
+        && match color {
+            Color::Red => pos.y >= 7,
+            Color::Black => pos.y <= 2,
+        }
+}
+
+/// The five palace points on the diagonals: the corners and the centre.
+fn is_advisor_point(pos: Pos, color: Color) -> bool {
+    let centre_row = match color {
+        Color::Red => 8,
+        Color::Black => 1,
+    };
+    in_palace(pos, color) && (pos.x == 4) == (pos.y == centre_row)
+}
+
+/// The seven points an Elephant can reach on its own side of the river.
+fn is_elephant_point(pos: Pos, color: Color) -> bool {
+    // Rows counted from the side's own back rank
+    let row = match color {
+        Color::Red => 9 - pos.y,
+        Color::Black => pos.y,
+    };
+    match row {
+        0 | 4 => pos.x == 2 || pos.x == 6,
+        2 => pos.x.is_multiple_of(4),
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::notation;
+    use std::collections::HashSet;
+
+    const START_FEN: &str = "rnbakabnr/9/1c5c1/p1p1p1p1p/9/9/P1P1P1P1P/1C5C1/9/RNBAKABNR w - - 0 1";
+
+    #[test]
+    fn fen_and_short_fen_of_start_position() {
+        let board = Board::new();
+        assert_eq!(board.to_fen(), START_FEN);
+        assert_eq!(
+            board.to_fen_short(),
+            "rnbakabnr/9/1c5c1/p1p1p1p1p/9/9/P1P1P1P1P/1C5C1/9/RNBAKABNR w"
+        );
+        assert!(board.to_fen().starts_with(&board.to_fen_short()));
+    }
+
+    fn pos(iccs: &str) -> Pos {
+        notation::iccs_to_pos(iccs).unwrap()
+    }
+
+    fn board(fen: &str) -> Board {
+        Board::from_fen(fen).unwrap()
+    }
+
+    #[test]
+    fn sole_defender_is_the_only_piece_that_can_answer_check() {
+        // The Red Chariot on d5 checks and the facing Generals rule out e9,
+        // so only Black's Chariot, by taking the checker, can answer
+        let board = board("3k5/9/9/9/r2R5/9/9/9/9/4K4 b");
+        assert!(board.is_in_check(Color::Black));
+        assert_eq!(board.sole_defender(), Some(pos("a5")));
+        assert_eq!(board.all_legal_moves(), vec![(pos("a5"), pos("d5"))]);
+    }
+
+    #[test]
+    fn no_sole_defender_out_of_check() {
+        assert_eq!(Board::new().sole_defender(), None);
+        // In check, but the General can step aside as well
+        let board = board("4k4/9/9/9/r3R4/9/9/9/9/3K5 b");
+        assert_eq!(board.sole_defender(), None);
+    }
+
+    #[test]
+    fn chariot_pins_horse_to_general() {
+        let board = board("3k5/9/4r4/9/9/9/9/4N4/9/4K4 w");
+        assert_eq!(board.pinned_pieces(Color::Red), vec![pos("e2")]);
+        assert!(board.legal_moves_from(pos("e2")).is_empty());
+        assert!(board.pinned_pieces(Color::Black).is_empty());
+    }
+
+    #[test]
+    fn halfmove_clock_counts_quiet_moves_and_resets_on_capture() {
+        let mut board = Board::new();
+        for (iccs, halfmove, fullmove) in [
+            ("h2e2", 1, 1),
+            ("h9g7", 2, 2),
+            ("e2e6", 0, 2),
+            ("g7e6", 0, 3),
+            ("b0c2", 1, 3),
+        ] {
+            let (from, to) = notation::iccs_to_move(iccs).unwrap();
+            board.try_move(from, to).unwrap();
+            assert_eq!(
+                (board.halfmove_clock, board.fullmove_number),
+                (halfmove, fullmove)
+            );
+        }
+        assert!(board.undo());
+        assert_eq!(board.halfmove_clock, 0);
+    }
+
+    #[test]
+    fn inactivity_draw_after_the_limit() {
+        let mut board = Board::new();
+        board.halfmove_clock = DEFAULT_INACTIVITY_LIMIT - 1;
+        assert!(!board.is_draw_by_inactivity());
+        board.halfmove_clock += 1;
+        assert!(board.is_draw_by_inactivity());
+    }
+
+    #[test]
+    fn classic_fortresses_are_recognised() {
+        // Lone Chariot against a full set of Advisors and Elephants
+        assert!(board("2bakab2/9/9/9/9/R8/9/9/9/3K5 w").is_likely_fortress());
+        // Lone Horse against two Advisors
+        assert!(board("3aka3/9/9/9/9/N8/9/9/9/3K5 w").is_likely_fortress());
+        // Bare Generals
+        assert!(board("4k4/9/9/9/9/9/9/9/9/3K5 w").is_likely_fortress());
+    }
+
+    #[test]
+    fn winning_material_is_not_a_fortress() {
+        // The Chariot wins once an Elephant is gone
+        assert!(!board("2baka3/9/9/9/9/R8/9/9/9/3K5 w").is_likely_fortress());
+        // Chariot and Horse against the full defence
+        assert!(!board("2bakab2/9/9/9/9/RN7/9/9/9/3K5 w").is_likely_fortress());
+        // A Soldier is never judged drawn
+        assert!(!board("4k4/9/9/9/9/P8/9/9/9/3K5 w").is_likely_fortress());
+        assert!(!Board::new().is_likely_fortress());
+    }
+
+    #[test]
+    fn from_pieces_places_exactly_the_given_pieces() {
+        let red = Piece::from_fen_char('K').unwrap();
+        let black = Piece::from_fen_char('k').unwrap();
+        let board =
+            Board::from_pieces(&[(pos("d0"), red), (pos("e9"), black)], Color::Black).unwrap();
+        assert_eq!(board.turn, Color::Black);
+        assert_eq!(board.state, GameState::Playing);
+        for y in 0..HEIGHT {
+            for x in 0..WIDTH {
+                let square = Pos::new(x, y);
+                let expected = if square == pos("d0") {
+                    Some(red)
+                } else if square == pos("e9") {
+                    Some(black)
+                } else {
+                    None
+                };
+                assert_eq!(board.get_piece(square), expected);
+            }
+        }
+
+        let twice = Board::from_pieces(&[(pos("d0"), red), (pos("d0"), black)], Color::Red);
+        assert!(matches!(twice, Err(BoardError::Occupied(square)) if square == pos("d0")));
+    }
+
+    #[test]
+    fn start_position_moves_grouped_by_type() {
+        let start = Board::new();
+        let groups = start.legal_moves_by_type(Color::Red);
+        for (piece_type, count) in [
+            (PieceType::General, 1),
+            (PieceType::Advisor, 2),
+            (PieceType::Elephant, 4),
+            (PieceType::Horse, 4),
+            (PieceType::Chariot, 4),
+            (PieceType::Cannon, 24),
+            (PieceType::Soldier, 5),
+        ] {
+            assert_eq!(groups[&piece_type].len(), count, "{:?}", piece_type);
+        }
+        let total: usize = groups.values().map(Vec::len).sum();
+        assert_eq!(total, start.all_legal_moves().len());
+
+        // A side with no moves for a type leaves it out
+        let board = board("4k4/9/9/9/9/9/9/9/9/3K5 w");
+        let groups = board.legal_moves_by_type(Color::Red);
+        assert_eq!(groups.keys().collect::<Vec<_>>(), vec![&PieceType::General]);
+    }
+
+    #[test]
+    fn two_red_generals_are_rejected() {
+        let fen = "4k4/9/9/9/9/9/9/9/3K5/5K3 w";
+        assert!(matches!(
+            Board::from_fen(fen),
+            Err(FenError::Invalid(errors))
+                if errors == vec![BoardError::TooManyPieces(Color::Red, PieceType::General)]
+        ));
+    }
+
+    #[test]
+    fn misplaced_elephants_are_rejected() {
+        let general = Piece::from_fen_char('K').unwrap();
+        let black_general = Piece::from_fen_char('k').unwrap();
+        let elephant = Piece::from_fen_char('B').unwrap();
+        let with_elephant = |square: &str| {
+            Board::from_pieces(
+                &[
+                    (pos("d0"), general),
+                    (pos("e9"), black_general),
+                    (pos(square), elephant),
+                ],
+                Color::Red,
+            )
+            .unwrap()
+            .validate()
+        };
+        assert_eq!(with_elephant("e2"), Ok(()));
+        assert_eq!(
+            with_elephant("d2"),
+            Err(vec![BoardError::ElephantOffSquare(pos("d2"))])
+        );
+        assert_eq!(
+            with_elephant("c5"),
+            Err(vec![BoardError::ElephantAcrossRiver(pos("c5"))])
+        );
+    }
+
+    fn pieces(list: &[(&str, char)], turn: Color) -> Board {
+        let pieces: Vec<(Pos, Piece)> = list
+            .iter()
+            .map(|&(square, c)| (pos(square), Piece::from_fen_char(c).unwrap()))
+            .collect();
+        Board::from_pieces(&pieces, turn).unwrap()
+    }
+
+    #[test]
+    fn generals_face_with_nothing_between() {
+        let board = pieces(&[("e0", 'K'), ("e9", 'k')], Color::Red);
+        assert!(board.generals_facing());
+        assert!(board.is_in_check(Color::Red));
+        assert!(board.is_in_check(Color::Black));
+        assert_eq!(board.validate(), Err(vec![BoardError::GeneralsFacing]));
+        // Different files never face
+        assert!(!pieces(&[("d0", 'K'), ("e9", 'k')], Color::Red).generals_facing());
+    }
+
+    #[test]
+    fn one_piece_between_blocks_the_generals() {
+        for blocker in ['N', 'n', 'P', 'p'] {
+            let board = pieces(&[("e0", 'K'), ("e5", blocker), ("e9", 'k')], Color::Red);
+            assert!(!board.generals_facing(), "{}", blocker);
+            assert!(!board.is_in_check(Color::Red));
+            assert!(!board.is_in_check(Color::Black));
+        }
+    }
+
+    #[test]
+    fn moves_may_not_expose_the_generals() {
+        // The Chariot is the only piece between the Generals
+        let mut board = pieces(&[("e0", 'K'), ("e2", 'R'), ("e9", 'k')], Color::Red);
+        assert_eq!(
+            board.explain_move(pos("e2"), pos("d2")),
+            Err(MoveError::LeavesGeneralInCheck)
+        );
+        assert!(!board.legal_moves_from(pos("e2")).contains(&pos("d2")));
+        // Along the file it still blocks
+        assert!(board.try_move(pos("e2"), pos("e6")).is_ok());
+
+        // Nor may a General step onto the open file
+        let board = pieces(&[("e0", 'K'), ("d9", 'k')], Color::Black);
+        assert_eq!(
+            board.explain_move(pos("d9"), pos("e9")),
+            Err(MoveError::LeavesGeneralInCheck)
+        );
+        assert_eq!(board.legal_moves_from(pos("d9")), vec![pos("d8")]);
+    }
+
+    #[test]
+    fn resigning_hands_the_game_over() {
+        let mut board = Board::new();
+        board.resign(Color::Red);
+        assert_eq!(board.state, GameState::Won(Color::Black));
+        assert_eq!(board.result_reason, Some(WinReason::Resignation));
+        assert_eq!(
+            board.try_move(pos("h2"), pos("e2")),
+            Err(MoveError::GameOver)
+        );
+    }
+
+    #[test]
+    fn draw_needs_the_other_side_to_accept() {
+        let mut board = Board::new();
+        assert!(board.offer_draw(Color::Red));
+        assert_eq!(board.state, GameState::Playing);
+        // The offering side can't accept its own offer
+        assert!(!board.accept_draw(Color::Red));
+        assert_eq!(board.state, GameState::Playing);
+        board.decline_draw();
+        assert!(!board.accept_draw(Color::Black));
+        assert_eq!(board.state, GameState::Playing);
+
+        board.offer_draw(Color::Red);
+        assert!(board.accept_draw(Color::Black));
+        assert_eq!(board.state, GameState::Draw);
+        assert_eq!(board.draw_offer, None);
+    }
+
+    #[test]
+    fn position_only_keeps_the_position_and_drops_history() {
+        let mut board = Board::new();
+        for (from, to) in [("h2", "e2"), ("h9", "g7"), ("e2", "e6")] {
+            board.try_move(pos(from), pos(to)).unwrap();
+        }
+        let mut copy = board.position_only();
+        assert!(copy.history.is_empty());
+        assert!(!copy.undo());
+        assert_eq!(copy.to_fen(), board.to_fen());
+        assert_eq!(copy.all_legal_moves(), board.all_legal_moves());
+        assert_eq!(crate::ai::evaluate(&copy), crate::ai::evaluate(&board));
+        // Still playable from there
+        copy.try_move(pos("g7"), pos("e6")).unwrap();
+        assert_eq!(copy.history.len(), 1);
+    }
+
+    #[test]
+    fn cannon_needs_a_screen_to_capture_even_next_door() {
+        let board = board("3k5/9/9/9/9/9/4p4/4C4/9/5K3 w");
+        assert_eq!(
+            board.explain_move(pos("e2"), pos("e3")),
+            Err(MoveError::NoScreen)
+        );
+        assert!(!board.legal_moves_from(pos("e2")).contains(&pos("e3")));
+    }
+
+    #[test]
+    fn cannon_slide_is_blocked_by_one_piece() {
+        // An own Soldier on e3, a Black Horse on e5 behind it
+        let board = board("4k4/9/9/9/4n4/9/4P4/4C4/9/3K5 w");
+        assert_eq!(
+            board.explain_move(pos("e2"), pos("e4")),
+            Err(MoveError::BlockedPath { at: pos("e3") })
+        );
+        // The same piece is the screen for a capture
+        assert_eq!(board.explain_move(pos("e2"), pos("e5")), Ok(()));
+        assert_eq!(board.explain_move(pos("e2"), pos("e1")), Ok(()));
+    }
+
+    #[test]
+    fn soldiers_turn_sideways_only_across_the_river() {
+        // A Red Soldier on row 5 (rank 4) is on its own bank; one step on,
+        // at row 4 (rank 5), it has crossed. Black mirrors this.
+        let red_bank = board("3k5/9/9/9/9/4P4/9/9/9/5K3 w");
+        assert_eq!(red_bank.legal_moves_from(pos("e4")), vec![pos("e5")]);
+        let red_across = board("3k5/9/9/9/4P4/9/9/9/9/5K3 w");
+        let mut moves = red_across.legal_moves_from(pos("e5"));
+        moves.sort_by_key(|pos| (pos.y, pos.x));
+        assert_eq!(moves, vec![pos("e6"), pos("d5"), pos("f5")]);
+
+        let black_bank = board("3k5/9/9/9/4p4/9/9/9/9/5K3 b");
+        assert_eq!(black_bank.legal_moves_from(pos("e5")), vec![pos("e4")]);
+        let black_across = board("3k5/9/9/9/9/4p4/9/9/9/5K3 b");
+        let mut moves = black_across.legal_moves_from(pos("e4"));
+        moves.sort_by_key(|pos| (pos.y, pos.x));
+        assert_eq!(moves, vec![pos("d4"), pos("f4"), pos("e3")]);
+    }
+
+    #[test]
+    fn start_position_has_44_moves() {
+        let board = Board::new();
+        let moves = board.all_legal_moves();
+        assert_eq!(moves.len(), 44);
+        assert!(
+            moves
+                .iter()
+                .all(|&(from, _)| board.get_piece(from).unwrap().color == Color::Red)
+        );
+        assert_eq!(board.perft(1), 44);
+        assert_eq!(board.perft(2), 1920);
+        assert_eq!(board.perft(3), 79666);
+    }
+
+    #[test]
+    fn snapshot_restores_a_game_in_progress() {
+        let mut board = Board::new();
+        for (from, to) in [("h2", "e2"), ("h9", "g7"), ("e2", "e6"), ("g7", "e6")] {
+            board.try_move(pos(from), pos(to)).unwrap();
+        }
+        board.offer_draw(Color::Red);
+        let snapshot = board.snapshot();
+        let fen = board.to_fen();
+
+        board.try_move(pos("b0"), pos("c2")).unwrap();
+        board.resign(Color::Black);
+        board.restore(snapshot.clone());
+        assert_eq!(board.snapshot(), snapshot);
+        assert_eq!(board.to_fen(), fen);
+        assert_eq!(board.state, GameState::Playing);
+        assert_eq!(board.draw_offer, Some(Color::Red));
+        // Captures are kept in the history, so undo brings the pieces back
+        let captured: Vec<_> = board
+            .history
+            .iter()
+            .filter_map(|entry| entry.captured)
+            .collect();
+        assert_eq!(captured.len(), 2);
+        while board.undo() {}
+        assert_eq!(board.to_fen(), START_FEN);
+    }
+
+    #[test]
+    fn cannon_pins_both_pieces_between_it_and_the_general() {
+        // Either piece leaving the file would make the other the screen
+        let pinned = board("3k5/9/4c4/9/9/4P4/9/4N4/9/4K4 w");
+        assert_eq!(pinned.pinned_pieces(Color::Red), vec![pos("e4"), pos("e2")]);
+        assert!(pinned.legal_moves_from(pos("e2")).is_empty());
+        // Stepping along the file keeps both in place
+        assert_eq!(pinned.legal_moves_from(pos("e4")), vec![pos("e5")]);
+
+        // A Chariot behind two pieces pins neither
+        let chariot = board("3k5/9/4r4/9/9/4P4/9/4N4/9/4K4 w");
+        assert!(chariot.pinned_pieces(Color::Red).is_empty());
+        // With one piece in front, the Cannon gives check instead of pinning
+        let check = board("3k5/9/4c4/9/9/9/9/4N4/9/4K4 w");
+        assert!(check.is_in_check(Color::Red));
+        assert!(check.pinned_pieces(Color::Red).is_empty());
+    }
+
+    #[test]
+    fn chariot_odds_for_black_removes_one_chariot() {
+        let start = Board::new();
+        let odds = Board::new_with_handicap(&[], &[PieceType::Chariot]);
+        assert_eq!(odds.material(Color::Black)[PieceType::Chariot as usize], 1);
+        assert_eq!(odds.material(Color::Red), start.material(Color::Red));
+        // Black's left-hand Chariot, on file i, is the one taken off
+        for y in 0..HEIGHT {
+            for x in 0..WIDTH {
+                let square = Pos::new(x, y);
+                if square == pos("i9") {
+                    assert_eq!(odds.get_piece(square), None);
+                } else {
+                    assert_eq!(
+                        odds.get_piece(square),
+                        start.get_piece(square),
+                        "{}",
+                        square
+                    );
+                }
+            }
+        }
+
+        let odds = Board::new_with_handicap(
+            &[PieceType::Horse, PieceType::Horse, PieceType::General],
+            &[],
+        );
+        assert_eq!(odds.material(Color::Red)[PieceType::Horse as usize], 0);
+        assert_eq!(odds.material(Color::Red)[PieceType::General as usize], 1);
+    }
+
+    #[test]
+    fn can_move_ignores_the_turn_but_move_piece_does_not() {
+        let mut start = Board::new();
+        // Black's Horse out of turn
+        assert!(start.can_move(pos("h9"), pos("g7")));
+        assert!(!start.move_piece(pos("h9"), pos("g7")));
+        assert_eq!(start.to_fen(), START_FEN);
+        // Illegal for either: a blocked Chariot
+        assert!(!start.can_move(pos("a0"), pos("a5")));
+        assert!(!start.move_piece(pos("a0"), pos("a5")));
+        // Legal and in turn: both agree
+        assert!(start.can_move(pos("h2"), pos("e2")));
+        assert!(start.move_piece(pos("h2"), pos("e2")));
+        assert!(start.can_move(pos("h0"), pos("g2")));
+        assert_eq!(start.turn, Color::Black);
+
+        // Self-check is refused even out of turn
+        let pinned = board("3k5/9/4r4/9/9/9/9/4N4/9/4K4 b");
+        assert!(!pinned.can_move(pos("e2"), pos("d4")));
+    }
+
+    #[test]
+    fn chariot_and_screened_cannon_both_attack() {
+        let screened = board("5k3/9/9/9/R8/9/4P4/9/4C4/3K5 w");
+        assert_eq!(screened.attack_count(pos("e5"), Color::Red), 2);
+        // Without the screen the Cannon only moves there, it can't capture
+        let open = board("5k3/9/9/9/R8/9/9/9/4C4/3K5 w");
+        assert_eq!(open.attack_count(pos("e5"), Color::Red), 1);
+        assert_eq!(open.attack_count(pos("e5"), Color::Black), 0);
+    }
+
+    #[test]
+    fn try_new_keeps_to_the_board() {
+        assert_eq!(Pos::try_new(0, 0), Some(Pos::new(0, 0)));
+        assert_eq!(Pos::try_new(8, 9), Some(Pos::new(8, 9)));
+        assert_eq!(Pos::try_new(-1, 0), None);
+        assert_eq!(Pos::try_new(0, -1), None);
+        assert_eq!(Pos::try_new(WIDTH as i32, 0), None);
+        assert_eq!(Pos::try_new(0, HEIGHT as i32), None);
+        assert!(!Pos::new(9, 0).in_bounds());
+    }
+
+    #[test]
+    fn only_generals_advisors_and_elephants_cannot_mate() {
+        assert!(board("3k5/9/9/9/9/9/9/9/9/4K4 w").is_insufficient_material());
+        assert!(board("2bk1ab2/4a4/9/9/9/9/9/9/9/3AK4 w").is_insufficient_material());
+        assert!(!board("2bak4/9/9/9/9/9/9/9/4C4/3AK4 w").is_insufficient_material());
+
+        // Taking the last attacking piece draws at once
+        let mut last = board("3k5/9/9/9/9/9/9/9/4p4/4K4 w");
+        last.try_move(pos("e0"), pos("e1")).unwrap();
+        assert_eq!(last.state, GameState::Draw);
+    }
+
+    #[test]
+    fn explain_move_names_the_first_broken_rule() {
+        let start = Board::new();
+        let explain = |board: &Board, from: &str, to: &str| board.explain_move(pos(from), pos(to));
+        assert_eq!(
+            explain(&start, "e4", "e5"),
+            Err(MoveError::NoPiece(pos("e4")))
+        );
+        assert_eq!(
+            explain(&start, "h7", "h6"),
+            Err(MoveError::NotYourTurn(pos("h7")))
+        );
+        assert_eq!(explain(&start, "e0", "e2"), Err(MoveError::InvalidMove));
+        assert_eq!(explain(&start, "d0", "c1"), Err(MoveError::LeavesPalace));
+        assert_eq!(explain(&start, "a0", "a0"), Err(MoveError::InvalidMove));
+        assert_eq!(
+            explain(&start, "a0", "a5"),
+            Err(MoveError::BlockedPath { at: pos("a3") })
+        );
+        assert_eq!(
+            explain(&start, "h2", "h8"),
+            Err(MoveError::BlockedPath { at: pos("h7") })
+        );
+        assert_eq!(
+            explain(&start, "b0", "d1"),
+            Err(MoveError::HorseLegBlocked { at: pos("c0") })
+        );
+        assert_eq!(explain(&start, "a3", "a2"), Err(MoveError::InvalidMove));
+        assert_eq!(
+            explain(&start, "e0", "d0"),
+            Err(MoveError::CapturesOwnPiece(pos("d0")))
+        );
+        assert_eq!(explain(&start, "h2", "e2"), Ok(()));
+
+        let elephants = board("3k5/9/9/9/9/2B6/9/9/3N5/2B1K4 w");
+        assert_eq!(
+            explain(&elephants, "c4", "e6"),
+            Err(MoveError::CrossesRiver)
+        );
+        assert_eq!(
+            explain(&elephants, "c0", "e2"),
+            Err(MoveError::ElephantEyeBlocked { at: pos("d1") })
+        );
+        assert_eq!(explain(&elephants, "c0", "a2"), Ok(()));
+    }
+
+    #[test]
+    fn symmetric_random_setups_are_valid_and_fair() {
+        fastrand::seed(7);
+        let mut setups = HashSet::new();
+        for _ in 0..50 {
+            let variant = Board::new_variant(VariantKind::SymmetricRandom);
+            assert_eq!(variant.validate(), Ok(()), "{}", variant.to_fen());
+            assert_eq!(
+                variant.get_piece(pos("e0")),
+                Board::new().get_piece(pos("e0"))
+            );
+            assert_eq!(
+                variant.get_piece(pos("e9")),
+                Board::new().get_piece(pos("e9"))
+            );
+            for y in 0..HEIGHT {
+                for x in 0..WIDTH {
+                    let piece = variant.grid[y][x];
+                    // Left matches right, and Black's side matches Red's
+                    assert_eq!(piece, variant.grid[y][WIDTH - 1 - x]);
+                    let black = variant.grid[HEIGHT - 1 - y][x].map(|p| Piece {
+                        color: p.color.opposite(),
+                        ..p
+                    });
+                    assert_eq!(piece, black, "{} at {:?}", variant.to_fen(), (x, y));
+                }
+            }
+            setups.insert(variant.to_fen());
+        }
+        assert!(setups.len() > 1);
+        assert_eq!(
+            Board::new_variant(VariantKind::Standard).to_fen(),
+            Board::new().to_fen()
+        );
+    }
+
+    #[test]
+    fn start_fen_loads_the_start_position() {
+        assert_eq!(board(START_FEN).snapshot(), Board::new().snapshot());
+        assert_eq!(
+            board("rnbakabnr/9/1c5c1/p1p1p1p1p/9/9/P1P1P1P1P/1C5C1/9/RNBAKABNR w").snapshot(),
+            Board::new().snapshot()
+        );
+    }
+
+    #[test]
+    fn counting_starts_below_the_threshold_and_draws_at_the_limit() {
+        let mut game = board("5k3/9/9/9/9/4p4/9/4R4/9/3K2N2 w");
+        game.counting = Some(CountingRules {
+            material_threshold: 2,
+            move_limit: 3,
+        });
+        // Chariot, Horse and Soldier are one too many
+        assert_eq!(game.counting_status(), None);
+
+        let mut counts = Vec::new();
+        for iccs in ["e2e4", "f9f8", "g0h2", "f8f9", "h2g0", "f9f8", "g0h2"] {
+            let (from, to) = notation::iccs_to_move(iccs).unwrap();
+            assert_eq!(game.state, GameState::Playing, "{}", iccs);
+            game.try_move(from, to).unwrap();
+            counts.push(game.counting_status());
+        }
+        assert_eq!(counts, [0, 0, 1, 1, 2, 2, 3].map(Some));
+        assert_eq!(game.state, GameState::Draw);
+        assert!(game.is_draw_by_counting());
+    }
+
+    #[test]
+    fn checking_back_and_forth_is_perpetual_check() {
+        let mut game = board("4k4/9/R8/9/9/9/9/9/9/3K5 w");
+        let play = |game: &mut Board, moves: &[&str]| {
+            for iccs in moves {
+                let (from, to) = notation::iccs_to_move(iccs).unwrap();
+                game.try_move(from, to).unwrap();
+            }
+        };
+        play(&mut game, &["a7a9"]);
+        let cycle = ["e9e8", "a9a8", "e8e9", "a8a9"];
+        play(&mut game, &cycle);
+        assert_eq!(game.repetition_kind(), None);
+        play(&mut game, &cycle);
+        assert_eq!(
+            game.repetition_kind(),
+            Some(RepetitionKind::PerpetualCheck(Color::Red))
+        );
+
+        // The same shuffle without checks is nobody's fault
+        let mut game = board("4k4/9/R8/9/9/9/9/9/9/3K5 w");
+        let quiet = ["a7a6", "e9e8", "a6a7", "e8e9"];
+        for _ in 0..2 {
+            play(&mut game, &quiet);
+        }
+        assert_eq!(game.repetition_kind(), Some(RepetitionKind::NeutralDraw));
+    }
+
+    #[test]
+    fn pieces_and_squares_display_as_on_the_board() {
+        let red_chariot = Piece {
+            color: Color::Red,
+            piece_type: PieceType::Chariot,
+        };
+        let black_general = Piece {
+            color: Color::Black,
+            piece_type: PieceType::General,
+        };
+        assert_eq!(format!("{}", red_chariot), "俥");
+        assert_eq!(format!("{}", black_general), "將");
+        assert_eq!(PieceType::Chariot.to_string(), "車");
+        assert_eq!(Color::default(), Color::Red);
+        assert_eq!(format!("{} {}", Color::Red, Color::Black), "紅 黑");
+        assert_eq!(pos("e3").to_string(), "e3");
+        assert_eq!(Pos::new(9, 0).to_string(), "(9, 0)");
+        assert_eq!(Board::default().to_fen(), START_FEN);
+    }
+
+    #[test]
+    fn mirroring_twice_gives_back_the_game() {
+        let mut game = Board::new();
+        for iccs in ["h2e2", "h9g7", "b0c2"] {
+            let (from, to) = notation::iccs_to_move(iccs).unwrap();
+            game.try_move(from, to).unwrap();
+        }
+        let mirrored = game.mirror_horizontal();
+        assert_ne!(mirrored.snapshot(), game.snapshot());
+        assert_eq!(mirrored.get_piece(pos("b2")), game.get_piece(pos("h2")));
+        assert_eq!(mirrored.turn, game.turn);
+        assert_eq!(mirrored.mirror_horizontal().snapshot(), game.snapshot());
+    }
+
+    #[test]
+    fn capturing_mate_reports_capture_check_and_game_over_in_order() {
+        let mut game = board("3k4n/R8/9/9/9/p8/9/9/9/4K3R w");
+        let events = std::sync::Arc::new(std::sync::Mutex::new(Vec::new()));
+        let sink = events.clone();
+        game.set_event_handler(move |event| sink.lock().unwrap().push(event));
+
+        // Copies stay silent
+        let mut copy = game.clone();
+        copy.try_move(pos("a8"), pos("a7")).unwrap();
+        assert!(events.lock().unwrap().is_empty());
+
+        game.try_move(pos("i0"), pos("i9")).unwrap();
+        assert_eq!(
+            *events.lock().unwrap(),
+            [
+                Event::Moved {
+                    from: pos("i0"),
+                    to: pos("i9")
+                },
+                Event::Captured(Piece {
+                    color: Color::Black,
+                    piece_type: PieceType::Horse
+                }),
+                Event::Check(Color::Black),
+                Event::GameOver(GameState::Won(Color::Red)),
+            ]
+        );
+    }
+
+    #[test]
+    fn generals_are_never_captured() {
+        // Random legal games keep both Generals to the end
+        let mut rng = fastrand::Rng::with_seed(576);
+        for _ in 0..20 {
+            let mut game = Board::new();
+            while game.state == GameState::Playing && game.history.len() < 300 {
+                let moves = game.all_legal_moves();
+                let (from, to) = moves[rng.usize(..moves.len())];
+                game.try_move(from, to).unwrap();
+                for color in [Color::Red, Color::Black] {
+                    assert!(game.find_general(color).is_some(), "{}", game.to_fen());
+                }
+            }
+        }
+
+        // Even handed a position where it could, the move is refused
+        let mut exposed = board("4k4/9/9/9/9/9/9/9/4R4/3K5 b");
+        exposed.turn = Color::Red;
+        assert_eq!(
+            exposed.try_move(pos("e1"), pos("e9")),
+            Err(MoveError::CapturesGeneral)
+        );
+        assert!(!exposed.all_legal_moves().contains(&(pos("e1"), pos("e9"))));
+        assert!(!exposed.move_piece(pos("e1"), pos("e9")));
+    }
+
+    #[test]
+    fn soldiers_on_the_last_rank_only_step_sideways() {
+        let red = board("P2k5/9/9/9/9/9/9/9/9/5K3 w");
+        assert_eq!(red.legal_moves_from(pos("a9")), vec![pos("b9")]);
+        assert_eq!(
+            red.explain_move(pos("a9"), pos("a8")),
+            Err(MoveError::InvalidMove)
+        );
+        let red = board("3k1P3/9/9/9/9/9/9/9/9/5K3 w");
+        let mut moves = red.legal_moves_from(pos("f9"));
+        moves.sort_by_key(|pos| pos.x);
+        assert_eq!(moves, vec![pos("e9"), pos("g9")]);
+
+        let black = board("3k5/9/9/9/9/9/9/9/9/5K2p b");
+        assert_eq!(black.legal_moves_from(pos("i0")), vec![pos("h0")]);
+        assert_eq!(
+            black.explain_move(pos("i0"), pos("i1")),
+            Err(MoveError::InvalidMove)
+        );
+    }
+
+    #[test]
+    fn apply_moves_rolls_back_on_the_first_refusal() {
+        let moves = |line: &[&str]| -> Vec<(Pos, Pos)> {
+            line.iter()
+                .map(|iccs| notation::iccs_to_move(iccs).unwrap())
+                .collect()
+        };
+        let mut game = Board::new();
+        game.try_move(pos("h2"), pos("e2")).unwrap();
+        game.selected = Some(pos("h9"));
+        let before = game.snapshot();
+
+        // Black's reply is played, then undone when Red's fails
+        assert_eq!(
+            game.apply_moves(&moves(&["h9g7", "b2e2", "c6c5"])),
+            Err((1, MoveError::CapturesOwnPiece(pos("e2"))))
+        );
+        assert_eq!(game.snapshot(), before);
+        assert_eq!(game.selected, Some(pos("h9")));
+
+        assert_eq!(
+            game.apply_moves(&moves(&["h9g7", "c6c5", "c3c4"])),
+            Err((1, MoveError::NotYourTurn(pos("c6"))))
+        );
+        assert_eq!(game.snapshot(), before);
+
+        assert_eq!(game.apply_moves(&moves(&["h9g7", "b2c2"])), Ok(()));
+        assert_eq!(game.history.len(), 3);
+    }
+
+    #[test]
+    fn elephants_reach_seven_squares_and_stop_at_a_blocked_eye() {
+        for (fen, start, squares) in [
+            (
+                "4k4/9/9/9/9/9/9/9/9/2B2K3 w",
+                "c0",
+                ["c0", "g0", "a2", "e2", "i2", "c4", "g4"],
+            ),
+            (
+                "2b1k4/9/9/9/9/9/9/9/9/5K3 b",
+                "c9",
+                ["c9", "g9", "a7", "e7", "i7", "c5", "g5"],
+            ),
+        ] {
+            // Every square the Elephant can walk to, one move at a time
+            let mut reached = vec![pos(start)];
+            let mut index = 0;
+            while index < reached.len() {
+                let mut walker = board(fen);
+                walker.grid[pos(start).y][pos(start).x] = None;
+                walker.grid[reached[index].y][reached[index].x] = board(fen).get_piece(pos(start));
+                for to in walker.legal_moves_from(reached[index]) {
+                    if !reached.contains(&to) {
+                        reached.push(to);
+                    }
+                }
+                index += 1;
+            }
+            reached.sort_by_key(|pos| (pos.y, pos.x));
+            let mut expected: Vec<Pos> = squares.iter().map(|square| pos(square)).collect();
+            expected.sort_by_key(|pos| (pos.y, pos.x));
+            assert_eq!(reached, expected, "{}", fen);
+        }
+
+        // A piece on the eye stops only the move across it
+        let blocked = board("4k4/9/9/9/9/9/9/9/1P7/2B2K3 w");
+        assert_eq!(blocked.legal_moves_from(pos("c0")), vec![pos("e2")]);
+        assert_eq!(
+            blocked.explain_move(pos("c0"), pos("a2")),
+            Err(MoveError::ElephantEyeBlocked { at: pos("b1") })
+        );
+    }
+
+    #[test]
+    fn results_record_how_the_game_was_won() {
+        let mut mate = board("3k4n/R8/9/9/9/p8/9/9/9/4K3R w");
+        mate.try_move(pos("i0"), pos("i9")).unwrap();
+        assert_eq!(mate.state, GameState::Won(Color::Red));
+        assert_eq!(mate.result_reason, Some(WinReason::Checkmate));
+        // Taking the move back takes the result with it
+        assert!(mate.undo());
+        assert_eq!(mate.result_reason, None);
+
+        let mut stalemate = board("3k5/9/R8/9/9/9/9/9/9/4K4 w");
+        stalemate.try_move(pos("a7"), pos("a8")).unwrap();
+        assert!(!stalemate.is_in_check(Color::Black));
+        assert_eq!(stalemate.state, GameState::Won(Color::Red));
+        assert_eq!(stalemate.result_reason, Some(WinReason::Stalemate));
+
+        let mut timeout = Board::new();
+        timeout.lose_on_time(Color::Red);
+        assert_eq!(timeout.state, GameState::Won(Color::Black));
+        assert_eq!(timeout.result_reason, Some(WinReason::Timeout));
+        // The first result stands
+        timeout.resign(Color::Black);
+        assert_eq!(timeout.result_reason, Some(WinReason::Timeout));
     }
 }
@@ -0,0 +1,114 @@
+use std::collections::HashMap;
+
+use eframe::egui::Key;
+
+/// Commands that can be given from the keyboard. The cursor keys, Enter,
+/// Space and Escape that move and play pieces are fixed and not listed.
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub enum Action {
+    Undo,
+    Hint,
+    NewGame,
+    Pause,
+    DebugOverlay,
+    Help,
+}
+
+impl Action {
+    pub const ALL: [Action; 6] = [
+        Action::Undo,
+        Action::Hint,
+        Action::NewGame,
+        Action::Pause,
+        Action::DebugOverlay,
+        Action::Help,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Action::Undo => "Undo",
+            Action::Hint => "Hint",
+            Action::NewGame => "New game",
+            Action::Pause => "Pause / resume",
+            Action::DebugOverlay => "Debug overlay",
+            Action::Help => "Keyboard shortcuts",
+        }
+    }
+}
+
+/// Key for every action, one key per action.
+pub type KeyBindings = HashMap<Action, Key>;
+
+pub fn default_bindings() -> KeyBindings {
+    HashMap::from([
+        (Action::Undo, Key::U),
+        (Action::Hint, Key::H),
+        (Action::NewGame, Key::N),
+        (Action::Pause, Key::P),
+        (Action::DebugOverlay, Key::F3),
+        (Action::Help, Key::Questionmark),
+    ])
+}
+
+/// The action bound to `key`, if any.
+pub fn action_for(bindings: &KeyBindings, key: Key) -> Option<Action> {
+    bindings
+        .iter()
+        .find(|&(_, &bound)| bound == key)
+        .map(|(&action, _)| action)
+}
+
+/// Binds `key` to `action`. An action that already had `key` takes over
+/// `action`'s old key, so that every action keeps exactly one key.
+pub fn rebind(bindings: &mut KeyBindings, action: Action, key: Key) {
+    let other = action_for(bindings, key).filter(|&other| other != action);
+    if let Some(old) = bindings.insert(action, key)
+        && let Some(other) = other
+    {
+        bindings.insert(other, old);
+    }
+}
+
+/// Keys with a fixed meaning on the board, which no action may take.
+pub fn is_reserved(key: Key) -> bool {
+    matches!(
+        key,
+        Key::ArrowLeft
+            | Key::ArrowRight
+            | Key::ArrowUp
+            | Key::ArrowDown
+            | Key::Enter
+            | Key::Space
+            | Key::Escape
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn each_default_key_maps_to_its_action() {
+        let bindings = default_bindings();
+        for action in Action::ALL {
+            assert_eq!(action_for(&bindings, bindings[&action]), Some(action));
+            assert!(!is_reserved(bindings[&action]));
+        }
+        assert_eq!(action_for(&bindings, Key::U), Some(Action::Undo));
+        assert_eq!(action_for(&bindings, Key::Z), None);
+    }
+
+    #[test]
+    fn rebinding_moves_the_key_and_swaps_on_conflict() {
+        let mut bindings = default_bindings();
+        rebind(&mut bindings, Action::Undo, Key::Z);
+        assert_eq!(action_for(&bindings, Key::Z), Some(Action::Undo));
+        assert_eq!(action_for(&bindings, Key::U), None);
+
+        // Hint's key goes to Undo, and Undo's old key to Hint
+        rebind(&mut bindings, Action::Undo, Key::H);
+        assert_eq!(bindings[&Action::Undo], Key::H);
+        assert_eq!(bindings[&Action::Hint], Key::Z);
+        assert_eq!(bindings.len(), Action::ALL.len());
+    }
+}
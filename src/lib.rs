@@ -0,0 +1,17 @@
+//! Xiangqi (Chinese chess) rules engine and AI, usable without the GUI.
+//! The egui front end lives in the `chinese-chess` binary behind the `gui`
+//! feature.
+
+pub mod ai;
+pub mod clock;
+pub mod code;
+pub mod diagram;
+pub mod game;
+pub mod movelog;
+pub mod net;
+pub mod notation;
+pub mod openings;
+pub mod record;
+pub mod save;
+pub mod tablebase;
+pub mod zobrist;
@@ -1,35 +1,72 @@
-mod game;
+mod keys;
+mod theme;
 
+use std::io;
+use std::path::Path;
 use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, TryRecvError};
+use std::thread;
+use std::time::Duration;
 
+use chinese_chess::ai::{self, Difficulty, EvalPerspective};
+use chinese_chess::clock::{self, Clock};
+use chinese_chess::diagram;
+use chinese_chess::game::{
+    Board, Color, CountingRules, GameState, HEIGHT, MoveError, Piece, PieceType, Pos, VariantKind,
+    WIDTH, WinReason,
+};
+use chinese_chess::movelog::{self, MoveRecord};
+use chinese_chess::net::{self, Connection, Message};
+use chinese_chess::notation;
+use chinese_chess::record;
+use chinese_chess::save::{self, UiState};
 use eframe::egui;
-use game::{Board, Color, GameState, PieceType, Pos};
+use keys::{Action, KeyBindings};
+use theme::Theme;
 
 fn main() -> eframe::Result<()> {
     let options = eframe::NativeOptions {
-        viewport: egui::ViewportBuilder::default().with_inner_size([600.0, 700.0]),
+        viewport: egui::ViewportBuilder::default()
+            .with_inner_size([600.0, 700.0])
+            .with_min_inner_size(MIN_WINDOW_SIZE),
         ..Default::default()
     };
     eframe::run_native(
         "Chinese Chess",
         options,
         Box::new(|cc| {
-            setup_custom_fonts(&cc.egui_ctx);
-            Ok(Box::new(ChessApp::new()))
+            let mut app = ChessApp::new();
+            match setup_custom_fonts(&cc.egui_ctx) {
+                Ok(font) => app.font = Some(font),
+                Err(message) => app.status = Some(message),
+            }
+            Ok(Box::new(app))
         }),
     )
 }
 
-fn setup_custom_fonts(ctx: &egui::Context) {
+/// Installs a CJK font for the piece glyphs and returns its data, for
+/// saved images. Without one, says why for the status line.
+fn setup_custom_fonts(ctx: &egui::Context) -> Result<Vec<u8>, String> {
+    let data = match diagram::load_cjk_font() {
+        Ok(data) => data,
+        Err(mut skipped) => {
+            // egui's default fonts still draw everything but the piece glyphs
+            skipped.push(format!(
+                "No CJK font found; set {} to a .ttf/.otf/.ttc file to show piece names",
+                diagram::FONT_ENV
+            ));
+            return Err(skipped.join("; "));
+        }
+    };
     let mut fonts = egui::FontDefinitions::default();
 
     // Install my own font (maybe supporting non-latin characters).
     // .ttf and .otf files supported.
     fonts.font_data.insert(
         "my_font".to_owned(),
-        Arc::new(egui::FontData::from_static(include_bytes!(
-            "/usr/share/fonts/opentype/noto/NotoSansCJK-Regular.ttc"
-        ))),
+        Arc::new(egui::FontData::from_owned(data.clone())),
     );
 
     // Put my font first (highest priority) for proportional text:
@@ -47,209 +84,2663 @@ fn setup_custom_fonts(ctx: &egui::Context) {
         .push("my_font".to_owned());
 
     ctx.set_fonts(fonts);
+    Ok(data)
 }
 
 struct ChessApp {
     board: Board,
+    /// Square of the piece currently being dragged, if any.
+    dragging: Option<Pos>,
+    /// Opt-in: pre-select the only piece able to answer a check.
+    auto_select: bool,
+    /// Hash of the position last auto-selected for, so that a cancelled
+    /// selection stays cancelled until the position changes.
+    auto_selected_for: Option<u64>,
+    clock: Clock,
+    /// Append every played move with its evaluations to `MOVE_LOG_PATH`.
+    log_moves: bool,
+    eval_perspective: EvalPerspective,
+    /// Keyboard cursor, moved with the arrow keys.
+    cursor: Pos,
+    /// Sides the computer plays, indexed by `Color as usize`. Both may be
+    /// set for the computer to play itself.
+    engine_controls: [bool; 2],
+    difficulty: Difficulty,
+    /// Bumped whenever the board is replaced, so that searches started on
+    /// an earlier board can be told apart.
+    generation: u64,
+    /// Computer move being searched in the background, if any.
+    search: Option<PendingSearch>,
+    /// Suggested move shown after pressing "Hint", until the next click
+    /// or move.
+    hint: Option<(Pos, Pos)>,
+    /// Address typed in for hosting or joining a network game.
+    net_address: String,
+    /// "Host" or "Join" waiting for the other player on a background
+    /// thread; yields the connection and the local side.
+    connecting: Option<mpsc::Receiver<io::Result<(Connection, Color)>>>,
+    network: Option<NetworkGame>,
+    theme: Theme,
+    /// Game opened from `GAME_PATH` for stepping through.
+    review: Option<Review>,
+    /// Time between moves when a review plays itself.
+    autoplay_interval: Duration,
+    puzzle: Puzzle,
+    /// Clock, board input and the computer are on hold.
+    paused: bool,
+    /// Mark pieces of either side that are pinned to their General.
+    show_pins: bool,
+    /// Draw the board upside down, Black at the bottom.
+    flipped: bool,
+    /// Shade each point by how many of the opponent's pieces attack it.
+    show_attacks: bool,
+    /// Starting setup for new games.
+    variant: VariantKind,
+    /// Index into `HANDICAPS` for new games.
+    handicap: usize,
+    /// Side playing without the handicap pieces.
+    odds_giver: Color,
+    /// Play new games under the tournament move-counting rule.
+    counting: bool,
+    /// Window title last sent, so it is only sent again when it changes.
+    title: String,
+    /// Why the player's last attempted move was refused, until the next
+    /// click or move.
+    illegal_move: Option<MoveError>,
+    /// Square clicked out of turn and the `egui` time of the click, outlined
+    /// for `FLASH_SECONDS`.
+    flash: Option<(Pos, f64)>,
+    /// Engine statistics drawn over the board.
+    debug_overlay: bool,
+    /// Analysis behind the overlay, for the position it was run on.
+    debug_analysis: Option<DebugAnalysis>,
+    keybindings: KeyBindings,
+    /// The keyboard shortcuts window is open.
+    show_help: bool,
+    /// Action waiting for its new key in the shortcuts window.
+    rebinding: Option<Action>,
+    /// Screen area of the board and its labels in the last frame.
+    board_rect: egui::Rect,
+    background: Option<BackgroundCache>,
+    /// "Are you sure?" is showing before a game in progress is discarded.
+    confirm_restart: bool,
+    /// FEN or short code typed in to play on from.
+    position: String,
+    /// Why the typed position was not loaded.
+    position_error: Option<String>,
+    /// A paste was asked of the clipboard and its text is yet to arrive.
+    awaiting_paste: bool,
+    /// File written by "Save image".
+    image_path: String,
+    /// The CJK font installed at startup, for the glyphs in saved images.
+    font: Option<Vec<u8>>,
+    /// Last file, connection or font problem, shown until dismissed.
+    status: Option<String>,
+}
+
+/// Mate puzzle practice: a position typed in as FEN, and the solution once
+/// asked for.
+struct Puzzle {
+    fen: String,
+    mate_in: u32,
+    /// Solution, or why there is none, shown under the puzzle controls.
+    message: Option<String>,
+}
+
+/// Positions of an opened game record, with the one on the board.
+struct Review {
+    positions: Vec<Board>,
+    index: usize,
+    /// Stepping forward on its own every `ChessApp::autoplay_interval`.
+    playing: bool,
+    /// `egui` time of the next automatic step.
+    next_step: f64,
+}
+
+/// A game against another player over the network.
+struct NetworkGame {
+    connection: Connection,
+    /// Side played on this machine; the other side's moves come from the
+    /// connection.
+    local: Color,
+}
+
+/// Quick analysis of one position for the debug overlay.
+struct DebugAnalysis {
+    /// [`Board::zobrist_hash`] of the position analysed.
+    key: u64,
+    /// Static evaluation, positive when Red is ahead.
+    score: i32,
+    best: Option<(Pos, Pos)>,
+    stats: ai::SearchStats,
+}
+
+/// Everything [`board_background`] depends on. The background looks the
+/// same either way up, so flipping the board doesn't change it.
+#[derive(Clone, Copy, PartialEq)]
+struct BackgroundKey {
+    offset: egui::Pos2,
+    cell_size: f32,
+    theme: Theme,
+    pixels_per_point: f32,
+}
+
+/// The board background as one ready-made mesh. Rebuilding and
+/// tessellating its few dozen shapes each frame is what this saves. The
+/// labels are left out: a glyph's texture coordinates point into the font
+/// atlas, which egui may rebuild at any frame, so a cached label could
+/// show the wrong glyph, and egui already caches the text layout anyway.
+/// Measured headless in a release build, a frame drawing only the
+/// background went from about 18.5µs to 10µs, tessellation included.
+struct BackgroundCache {
+    key: BackgroundKey,
+    mesh: Arc<egui::Mesh>,
+}
+
+impl BackgroundCache {
+    fn new(ctx: &egui::Context, key: BackgroundKey) -> Self {
+        let options = ctx.tessellation_options(|options| *options);
+        // No text or circles, so the font texture isn't needed
+        let mut tessellator =
+            egui::epaint::Tessellator::new(key.pixels_per_point, options, [1, 1], Vec::new());
+        let mut mesh = egui::Mesh::default();
+        for shape in board_background(&key.theme, key.offset, key.cell_size) {
+            tessellator.tessellate_shape(shape, &mut mesh);
+        }
+        BackgroundCache {
+            key,
+            mesh: Arc::new(mesh),
+        }
+    }
 }
 
+/// A computer move search running on a background thread.
+struct PendingSearch {
+    /// `ChessApp::generation` when the search started.
+    generation: u64,
+    receiver: mpsc::Receiver<Option<(Pos, Pos)>>,
+    /// Raised to make the search answer with its best move so far.
+    stop: Arc<AtomicBool>,
+}
+
+const MOVE_LOG_PATH: &str = "move_log.csv";
+const SAVE_PATH: &str = "savegame.txt";
+const GAME_PATH: &str = "game.txt";
+/// Odds presets for new games: the pieces the giving side starts without.
+const HANDICAPS: [(&str, &[PieceType]); 4] = [
+    ("Even", &[]),
+    ("Horse odds", &[PieceType::Horse]),
+    ("Chariot odds", &[PieceType::Chariot]),
+    ("Two-piece odds", &[PieceType::Horse, PieceType::Horse]),
+];
+/// Search depth for hints. Hints are searched on the UI thread, so this
+/// is kept shallow.
+const HINT_DEPTH: u32 = 2;
+/// Smallest square size, in points, at which the piece characters stay
+/// readable. Below it the board keeps this size and is cut off instead.
+const MIN_CELL_SIZE: f32 = 28.0;
+/// Height the controls above the board always get; beyond it they scroll
+/// to leave room for a board of `MIN_CELL_SIZE` squares.
+const MIN_CONTROLS_HEIGHT: f32 = 120.0;
+/// Smallest usable window: a board of `MIN_CELL_SIZE` squares (10 by 11
+/// squares with the labels), `MIN_CONTROLS_HEIGHT` of controls and the
+/// move list beside it.
+const MIN_WINDOW_SIZE: [f32; 2] = [520.0, 460.0];
+/// How long a square clicked out of turn stays outlined.
+const FLASH_SECONDS: f64 = 0.3;
+/// Search depth for the debug overlay, searched on the UI thread like
+/// hints.
+const DEBUG_DEPTH: u32 = 2;
+
 impl ChessApp {
     fn new() -> Self {
         Self {
             board: Board::new(),
+            dragging: None,
+            auto_select: false,
+            auto_selected_for: None,
+            clock: Self::new_clock(),
+            log_moves: false,
+            eval_perspective: EvalPerspective::Red,
+            cursor: Pos::new(4, 9),
+            engine_controls: [false; 2],
+            difficulty: Difficulty::Medium,
+            generation: 0,
+            search: None,
+            hint: None,
+            net_address: "127.0.0.1:7878".to_string(),
+            connecting: None,
+            network: None,
+            theme: Theme::default(),
+            review: None,
+            autoplay_interval: Duration::from_secs(1),
+            puzzle: Puzzle {
+                fen: String::new(),
+                mate_in: 2,
+                message: None,
+            },
+            paused: false,
+            show_pins: false,
+            flipped: false,
+            show_attacks: false,
+            variant: VariantKind::Standard,
+            handicap: 0,
+            odds_giver: Color::Red,
+            counting: false,
+            title: String::new(),
+            illegal_move: None,
+            flash: None,
+            debug_overlay: false,
+            debug_analysis: None,
+            keybindings: keys::default_bindings(),
+            show_help: false,
+            rebinding: None,
+            board_rect: egui::Rect::NOTHING,
+            background: None,
+            confirm_restart: false,
+            position: String::new(),
+            position_error: None,
+            awaiting_paste: false,
+            image_path: "board.png".to_string(),
+            font: None,
+            status: None,
         }
     }
-}
 
-impl eframe::App for ChessApp {
-    fn update(&mut self, ctx: &egui::Context, _frame: &mut eframe::Frame) {
-        egui::CentralPanel::default().show(ctx, |ui| {
-            ui.heading("Chinese Chess");
-            match self.board.state {
-                GameState::Playing => {
-                    ui.label(format!("Turn: {:?}", self.board.turn));
-                }
-                GameState::Won(winner) => {
-                    ui.label(
-                        egui::RichText::new(format!("{:?} Wins!", winner))
-                            .color(egui::Color32::GOLD)
-                            .size(20.0),
-                    );
-                    if ui.button("Restart").clicked() {
-                        self.board = Board::new();
+    /// Starts a new game on `board`, dropping anything tied to the old one.
+    fn set_board(&mut self, board: Board) {
+        self.board = board;
+        self.review = None;
+        self.new_generation();
+        self.hint = None;
+        self.dragging = None;
+        self.illegal_move = None;
+        self.paused = false;
+        self.clock = Self::new_clock();
+        self.clock.running = Some(self.board.turn);
+    }
+
+    fn computer_to_move(&self) -> bool {
+        self.engine_controls[self.board.turn as usize]
+            && self.network.is_none()
+            && !self.review.as_ref().is_some_and(|review| review.playing)
+            && self.board.state == GameState::Playing
+    }
+
+    fn remote_to_move(&self) -> bool {
+        self.board.state == GameState::Playing
+            && self
+                .network
+                .as_ref()
+                .is_some_and(|network| network.local != self.board.turn)
+    }
+
+    /// Sends `message` to the other player, if this is a network game.
+    fn send(&mut self, message: Message) {
+        if let Some(network) = &mut self.network
+            && let Err(err) = network.connection.send(message)
+        {
+            self.status = Some(format!("Connection lost: {}", err));
+            self.network = None;
+        }
+    }
+
+    /// Hosts (local Red) or joins (local Black) a network game at
+    /// `net_address` on a background thread.
+    fn connect(&mut self, local: Color) {
+        let (sender, receiver) = mpsc::channel();
+        let addr = self.net_address.clone();
+        thread::spawn(move || {
+            let connection = match local {
+                Color::Red => net::host(addr),
+                Color::Black => net::join(addr),
+            };
+            let _ = sender.send(connection.map(|connection| (connection, local)));
+        });
+        self.connecting = Some(receiver);
+    }
+
+    fn poll_connecting(&mut self) {
+        let Some(receiver) = &self.connecting else {
+            return;
+        };
+        let result = match receiver.try_recv() {
+            Ok(result) => result,
+            Err(TryRecvError::Empty) => return,
+            Err(TryRecvError::Disconnected) => Err(io::ErrorKind::ConnectionAborted.into()),
+        };
+        self.connecting = None;
+        match result {
+            Ok((connection, local)) => {
+                self.engine_controls = [false; 2];
+                self.network = Some(NetworkGame { connection, local });
+                self.set_board(Board::new());
+            }
+            Err(err) => {
+                self.status = Some(format!(
+                    "Failed to connect to {}: {}",
+                    self.net_address, err
+                ));
+            }
+        }
+    }
+
+    /// Applies whatever the other player has sent. Moves out of turn or
+    /// illegal on our board are ignored rather than trusted.
+    fn poll_network(&mut self) {
+        loop {
+            let Some(network) = &mut self.network else {
+                return;
+            };
+            let remote = network.local.opposite();
+            let message = match network.connection.poll_message() {
+                Ok(Some(message)) => message,
+                Ok(None) => return,
+                Err(err) => {
+                    self.status = Some(format!("Connection lost: {}", err));
+                    self.network = None;
+                    return;
+                }
+            };
+            match message {
+                Message::Move(from, to) => {
+                    if self.board.turn != remote || !self.play_move(from, to) {
+                        self.status = Some(format!(
+                            "Ignored invalid move {} from the other player",
+                            notation::move_to_iccs(from, to)
+                        ));
+                    }
+                }
+                Message::Resign => {
+                    self.board.resign(remote);
+                    self.clock.stop();
+                }
+                Message::OfferDraw => {
+                    self.board.offer_draw(remote);
+                }
+                Message::AcceptDraw => {
+                    if self.board.accept_draw(remote) {
+                        self.clock.stop();
+                    }
+                }
+                Message::DeclineDraw => {
+                    if self.board.draw_offer == Some(remote.opposite()) {
+                        self.board.decline_draw();
                     }
                 }
             }
+        }
+    }
 
-            let available_size = ui.available_size();
-            let board_width = available_size.x.min(available_size.y * 0.9);
-            let cell_size = board_width / 10.0;
+    /// Starting position for a new game with the chosen setup and odds.
+    fn new_game_board(&self) -> Board {
+        let mut board = Board::new_variant(self.variant);
+        let remove = HANDICAPS[self.handicap].1;
+        match self.odds_giver {
+            Color::Red => board.remove_handicap(remove, &[]),
+            Color::Black => board.remove_handicap(&[], remove),
+        }
+        board.counting = self.counting.then(CountingRules::default);
+        board
+    }
 
-            let (response, painter) = ui.allocate_painter(available_size, egui::Sense::click());
+    /// Starts a new game. Both sides of a network game start from the
+    /// standard position.
+    fn restart(&mut self) {
+        let board = if self.network.is_some() {
+            Board::new()
+        } else {
+            self.new_game_board()
+        };
+        self.set_board(board);
+    }
 
-            let offset = response.rect.min
-                + egui::vec2(
-                    (available_size.x - board_width) / 2.0 + cell_size / 2.0,
-                    50.0,
-                );
+    /// Restarts straight away when nothing would be lost, otherwise asks
+    /// first.
+    fn request_restart(&mut self) {
+        if self.board.state == GameState::Playing && !self.board.history.is_empty() {
+            self.confirm_restart = true;
+        } else {
+            self.restart();
+        }
+    }
 
-            // Draw grid
-            let stroke = egui::Stroke::new(1.0, egui::Color32::BLACK);
+    fn thinking(&self) -> bool {
+        self.search
+            .as_ref()
+            .is_some_and(|search| search.generation == self.generation)
+    }
 
-            // Horizontal lines
-            for y in 0..10 {
-                let start = offset + egui::vec2(0.0, y as f32 * cell_size);
-                let end = offset + egui::vec2(8.0 * cell_size, y as f32 * cell_size);
-                painter.line_segment([start, end], stroke);
+    /// Marks results of searches started so far as stale, and stops them
+    /// so their threads don't keep running for nothing.
+    fn new_generation(&mut self) {
+        self.generation += 1;
+        self.stop_search();
+    }
+
+    /// Asks the running search, if any, for its best move so far.
+    fn stop_search(&self) {
+        if let Some(search) = &self.search {
+            search.stop.store(true, Ordering::Relaxed);
+        }
+    }
+
+    /// Starts searching for the computer's move on a background thread.
+    fn start_computer_move(&mut self) {
+        let (sender, receiver) = mpsc::channel();
+        let board = self.board.position_only();
+        let difficulty = self.difficulty;
+        let stop = Arc::new(AtomicBool::new(false));
+        let search_stop = stop.clone();
+        thread::spawn(move || {
+            let result = ai::best_move_with_difficulty_and_stop(
+                &board,
+                board.turn,
+                difficulty,
+                ai::DEFAULT_MOVE_TIME,
+                &search_stop,
+            );
+            // The app may have dropped the receiver by now; nothing to do then
+            let _ = sender.send(result);
+        });
+        self.search = Some(PendingSearch {
+            generation: self.generation,
+            receiver,
+            stop,
+        });
+    }
+
+    /// Plays the computer's move once its search has finished. Results of
+    /// searches started on an earlier board are dropped.
+    fn poll_computer_move(&mut self, ctx: &egui::Context) {
+        let Some(search) = &self.search else {
+            return;
+        };
+        let result = match search.receiver.try_recv() {
+            Ok(result) => result,
+            Err(TryRecvError::Empty) => {
+                ctx.request_repaint();
+                return;
+            }
+            Err(TryRecvError::Disconnected) => None,
+        };
+        let generation = search.generation;
+        self.search = None;
+        if generation == self.generation
+            && self.computer_to_move()
+            && let Some((from, to)) = result
+        {
+            self.play_move(from, to);
+        }
+    }
+
+    /// The background mesh for `key`, rebuilt only when `key` differs from
+    /// the cached one's.
+    fn background_mesh(&mut self, ctx: &egui::Context, key: BackgroundKey) -> Arc<egui::Mesh> {
+        match &self.background {
+            Some(cache) if cache.key == key => cache.mesh.clone(),
+            _ => {
+                let cache = BackgroundCache::new(ctx, key);
+                let mesh = cache.mesh.clone();
+                self.background = Some(cache);
+                mesh
             }
+        }
+    }
 
-            // Vertical lines
-            for x in 0..9 {
-                let start_top = offset + egui::vec2(x as f32 * cell_size, 0.0);
-                let end_top = offset + egui::vec2(x as f32 * cell_size, 4.0 * cell_size);
-                painter.line_segment([start_top, end_top], stroke);
+    /// Plays the move of a finished search, then starts the next search if
+    /// the computer is to move and none is running for this position.
+    /// Returns whether the computer is to move.
+    fn drive_computer(&mut self, ctx: &egui::Context) -> bool {
+        self.poll_computer_move(ctx);
+        if !self.computer_to_move() {
+            return false;
+        }
+        if !self.thinking() {
+            self.start_computer_move();
+        }
+        true
+    }
+
+    fn new_clock() -> Clock {
+        Clock::new(Duration::from_secs(10 * 60), None).with_overtime(3, 30_000)
+    }
+
+    fn can_hint(&self) -> bool {
+        self.board.state == GameState::Playing
+            && !self.paused
+            && !self.computer_to_move()
+            && !self.remote_to_move()
+    }
+
+    /// Carries out a keyboard shortcut, when the button it stands for
+    /// would be enabled.
+    fn perform(&mut self, action: Action) {
+        match action {
+            Action::Undo => {
+                if self.network.is_none() {
+                    self.undo_move();
+                }
+            }
+            Action::Hint => {
+                if self.can_hint() {
+                    self.hint = ai::best_move(&self.board, self.board.turn, HINT_DEPTH);
+                }
+            }
+            Action::NewGame => {
+                if self.network.is_none() {
+                    self.request_restart();
+                }
+            }
+            Action::Pause => {
+                if self.network.is_none() && self.board.state == GameState::Playing {
+                    self.paused = !self.paused;
+                    self.dragging = None;
+                }
+            }
+            Action::DebugOverlay => self.debug_overlay = !self.debug_overlay,
+            Action::Help => self.show_help = !self.show_help,
+        }
+    }
 
-                let start_bottom = offset + egui::vec2(x as f32 * cell_size, 5.0 * cell_size);
-                let end_bottom = offset + egui::vec2(x as f32 * cell_size, 9.0 * cell_size);
-                painter.line_segment([start_bottom, end_bottom], stroke);
+    /// Dispatches key presses through `keybindings`, or hands the first
+    /// one to the action being rebound. Shortcuts are off while a text
+    /// field has the keyboard or the restart dialog is open.
+    fn handle_shortcuts(&mut self, ctx: &egui::Context) {
+        let pressed: Vec<egui::Key> = ctx.input(|i| {
+            i.events
+                .iter()
+                .filter_map(|event| match event {
+                    egui::Event::Key {
+                        key,
+                        pressed: true,
+                        repeat: false,
+                        ..
+                    } => Some(*key),
+                    _ => None,
+                })
+                .collect()
+        });
+        for key in pressed {
+            if let Some(action) = self.rebinding {
+                if key == egui::Key::Escape {
+                    self.rebinding = None;
+                } else if !keys::is_reserved(key) {
+                    keys::rebind(&mut self.keybindings, action, key);
+                    self.rebinding = None;
+                }
+            } else if !ctx.wants_keyboard_input()
+                && !self.confirm_restart
+                && let Some(action) = keys::action_for(&self.keybindings, key)
+            {
+                self.perform(action);
             }
+        }
+    }
 
-            // River boundaries
-            let river_left_start = offset + egui::vec2(0.0, 4.0 * cell_size);
-            let river_left_end = offset + egui::vec2(0.0, 5.0 * cell_size);
-            painter.line_segment([river_left_start, river_left_end], stroke);
+    /// Window listing every shortcut, with a button per action to change
+    /// its key.
+    fn shortcuts_window(&mut self, ctx: &egui::Context) {
+        let mut open = self.show_help;
+        egui::Window::new("Keyboard shortcuts")
+            .open(&mut open)
+            .collapsible(false)
+            .resizable(false)
+            .show(ctx, |ui| {
+                egui::Grid::new("shortcuts").striped(true).show(ui, |ui| {
+                    for action in Action::ALL {
+                        ui.label(action.name());
+                        let key = if self.rebinding == Some(action) {
+                            "Press a key…"
+                        } else {
+                            self.keybindings[&action].name()
+                        };
+                        if ui.button(key).clicked() {
+                            self.rebinding = Some(action);
+                        }
+                        ui.end_row();
+                    }
+                });
+                ui.label("Click a key to change it, then press the new one. Escape cancels.");
+                ui.label("The arrow keys, Enter, Space and Escape are fixed.");
+                if ui.button("Restore defaults").clicked() {
+                    self.keybindings = keys::default_bindings();
+                    self.rebinding = None;
+                }
+            });
+        self.show_help = open;
+        if !open {
+            self.rebinding = None;
+        }
+    }
 
-            let river_right_start = offset + egui::vec2(8.0 * cell_size, 4.0 * cell_size);
-            let river_right_end = offset + egui::vec2(8.0 * cell_size, 5.0 * cell_size);
-            painter.line_segment([river_right_start, river_right_end], stroke);
+    /// Analyses the position for the debug overlay, unless the last
+    /// analysis was of this same position.
+    fn refresh_debug_analysis(&mut self) {
+        let key = self.board.zobrist_hash();
+        if self
+            .debug_analysis
+            .as_ref()
+            .is_some_and(|analysis| analysis.key == key)
+        {
+            return;
+        }
+        let (best, stats) = ai::best_move_with_stats(&self.board, self.board.turn, DEBUG_DEPTH);
+        self.debug_analysis = Some(DebugAnalysis {
+            key,
+            score: ai::evaluate(&self.board),
+            best,
+            stats,
+        });
+    }
 
-            // Palace diagonals
-            // Top (Black)
-            painter.line_segment(
-                [
-                    offset + egui::vec2(3.0 * cell_size, 0.0),
-                    offset + egui::vec2(5.0 * cell_size, 2.0 * cell_size),
-                ],
-                stroke,
-            );
-            painter.line_segment(
-                [
-                    offset + egui::vec2(5.0 * cell_size, 0.0),
-                    offset + egui::vec2(3.0 * cell_size, 2.0 * cell_size),
-                ],
-                stroke,
-            );
+    /// Whether a click on `pos` is out of turn and should flash instead of
+    /// doing anything. That is a click on a piece of the side not to move,
+    /// with nothing selected, while the game is on and not paused. It
+    /// covers clicking the opponent's pieces on your own turn, and clicking
+    /// your own while the computer or the other player is to move. With a
+    /// piece selected, clicking an enemy piece is a capture attempt and is
+    /// explained like any other illegal move instead.
+    fn is_out_of_turn_click(&self, pos: Pos) -> bool {
+        self.board.state == GameState::Playing
+            && !self.paused
+            && !self.confirm_restart
+            && self.board.selected.is_none()
+            && self
+                .board
+                .get_piece(pos)
+                .is_some_and(|piece| piece.color != self.board.turn)
+    }
 
-            // Bottom (Red)
-            painter.line_segment(
-                [
-                    offset + egui::vec2(3.0 * cell_size, 7.0 * cell_size),
-                    offset + egui::vec2(5.0 * cell_size, 9.0 * cell_size),
-                ],
-                stroke,
+    /// With auto-select on, selects the only piece able to answer a check,
+    /// once per position and only while a human is to move.
+    fn auto_select_defender(&mut self) {
+        if !self.auto_select
+            || self.board.state != GameState::Playing
+            || self.paused
+            || self.confirm_restart
+            || self.computer_to_move()
+            || self.remote_to_move()
+            || self.dragging.is_some()
+        {
+            return;
+        }
+        let key = self.board.zobrist_hash();
+        if self.auto_selected_for == Some(key) {
+            return;
+        }
+        self.auto_selected_for = Some(key);
+        if self.board.selected.is_none() {
+            self.board.selected = self.board.sole_defender();
+        }
+    }
+
+    /// Click (or Enter/Space) on `pos`: selects an own piece, or moves the
+    /// selected piece there.
+    fn activate(&mut self, pos: Pos) {
+        self.hint = None;
+        self.illegal_move = None;
+        if let Some(selected) = self.board.selected {
+            if self.play_move(selected, pos) {
+                self.board.selected = None;
+            } else if let Some(piece) = self.board.get_piece(pos)
+                && piece.color == self.board.turn
+            {
+                self.board.selected = Some(pos);
+            } else {
+                self.illegal_move = self.board.explain_move(selected, pos).err();
+                self.board.selected = None;
+            }
+        } else if let Some(piece) = self.board.get_piece(pos)
+            && piece.color == self.board.turn
+        {
+            self.board.selected = Some(pos);
+        }
+    }
+
+    /// Arrow keys move the cursor, Enter/Space act like a click on it and
+    /// Escape clears the selection and hint, like a right-click.
+    fn handle_keyboard(&mut self, ctx: &egui::Context) {
+        let (left, right, up, down, activate, cancel) = ctx.input(|i| {
+            (
+                i.key_pressed(egui::Key::ArrowLeft),
+                i.key_pressed(egui::Key::ArrowRight),
+                i.key_pressed(egui::Key::ArrowUp),
+                i.key_pressed(egui::Key::ArrowDown),
+                i.key_pressed(egui::Key::Enter) || i.key_pressed(egui::Key::Space),
+                i.key_pressed(egui::Key::Escape),
+            )
+        });
+        // The cursor moves as drawn, whichever way up the board is
+        let (left, right, up, down) = if self.flipped {
+            (right, left, down, up)
+        } else {
+            (left, right, up, down)
+        };
+        if left {
+            self.cursor.x = self.cursor.x.saturating_sub(1);
+        }
+        if right {
+            self.cursor.x = (self.cursor.x + 1).min(WIDTH - 1);
+        }
+        if up {
+            self.cursor.y = self.cursor.y.saturating_sub(1);
+        }
+        if down {
+            self.cursor.y = (self.cursor.y + 1).min(HEIGHT - 1);
+        }
+        if activate {
+            self.activate(self.cursor);
+        }
+        if cancel {
+            self.board.selected = None;
+            self.hint = None;
+        }
+    }
+
+    fn save_to_file(&mut self) {
+        let ui = UiState {
+            selected: self.board.selected,
+            flipped: self.flipped,
+        };
+        if let Err(err) = std::fs::write(SAVE_PATH, save::save_game(&self.board, Some(&ui))) {
+            self.status = Some(format!("Failed to write {}: {}", SAVE_PATH, err));
+        }
+    }
+
+    /// Plays on from the typed position, read as FEN if it has a '/' and as
+    /// a short code otherwise. Invalid or already decided positions leave
+    /// the board alone and say why.
+    fn load_position(&mut self) {
+        let text = self.position.trim();
+        let board = if text.contains('/') {
+            Board::from_fen(text).map_err(|err| format!("Invalid FEN: {}", err))
+        } else {
+            Board::from_code(text).map_err(|err| format!("Invalid code: {}", err))
+        };
+        match board {
+            Ok(board) if board.state != GameState::Playing => {
+                self.position_error =
+                    Some("The game is already over in that position.".to_string());
+            }
+            Ok(board) => {
+                self.set_board(board);
+                self.position_error = None;
+            }
+            Err(err) => self.position_error = Some(err),
+        }
+    }
+
+    /// Loads the clipboard text asked for by the "Paste" button once the
+    /// platform hands it over.
+    fn poll_paste(&mut self, ctx: &egui::Context) {
+        if !self.awaiting_paste {
+            return;
+        }
+        let pasted = ctx.input(|i| {
+            i.events.iter().find_map(|event| match event {
+                egui::Event::Paste(text) => Some(text.clone()),
+                _ => None,
+            })
+        });
+        if let Some(text) = pasted {
+            self.awaiting_paste = false;
+            self.position = text;
+            self.load_position();
+        }
+    }
+
+    fn load_puzzle(&mut self) {
+        match Board::from_fen(self.puzzle.fen.trim()) {
+            Ok(board) => {
+                self.set_board(board);
+                self.puzzle.message = None;
+            }
+            Err(err) => self.puzzle.message = Some(format!("Invalid FEN: {}", err)),
+        }
+    }
+
+    fn solve_puzzle(&mut self) {
+        let mate_in = self.puzzle.mate_in;
+        self.puzzle.message = Some(match ai::find_mate(&self.board, self.board.turn, mate_in) {
+            Some(line) => {
+                let moves: Vec<String> = line
+                    .iter()
+                    .map(|&(from, to)| notation::move_to_iccs(from, to))
+                    .collect();
+                format!("Solution: {}", moves.join(" "))
+            }
+            None => format!("No mate in {} for {:?}", mate_in, self.board.turn),
+        });
+    }
+
+    /// Draws the board as a PNG at `image_path`, independent of the window.
+    fn save_board_image(&mut self) {
+        let png = diagram::to_png(&self.board, self.font.as_deref());
+        if let Err(err) = std::fs::write(&self.image_path, png) {
+            self.status = Some(format!("Failed to write {}: {}", self.image_path, err));
+        }
+    }
+
+    fn export_game(&mut self) {
+        if let Err(err) = std::fs::write(GAME_PATH, self.board.export_game()) {
+            self.status = Some(format!("Failed to write {}: {}", GAME_PATH, err));
+        }
+    }
+
+    /// Opens the game record at `GAME_PATH`, showing its final position
+    /// with controls to step back through it.
+    fn open_game(&mut self) {
+        let text = match std::fs::read_to_string(GAME_PATH) {
+            Ok(text) => text,
+            Err(err) => {
+                self.status = Some(format!("Failed to read {}: {}", GAME_PATH, err));
+                return;
+            }
+        };
+        match record::import_game(&text) {
+            Ok(board) => {
+                let positions = board.positions();
+                let index = positions.len() - 1;
+                self.set_board(board);
+                self.review = Some(Review {
+                    positions,
+                    index,
+                    playing: false,
+                    next_step: 0.0,
+                });
+            }
+            Err(err) => self.status = Some(format!("Failed to open {}: {}", GAME_PATH, err)),
+        }
+    }
+
+    /// Shows position `index` of the game under review. Play can continue
+    /// from there, which ends the review.
+    fn review_step(&mut self, index: usize) {
+        let Some(review) = self.review.take() else {
+            return;
+        };
+        if let Some(board) = review.positions.get(index) {
+            self.set_board(board.clone());
+        }
+        self.review = Some(Review {
+            index: index.min(review.positions.len() - 1),
+            ..review
+        });
+    }
+
+    /// Like [`ChessApp::review_step`], for steps taken by hand, which stop
+    /// any playback so the two don't fight over the position.
+    fn review_step_manually(&mut self, index: usize) {
+        if let Some(review) = &mut self.review {
+            review.playing = false;
+        }
+        self.review_step(index);
+    }
+
+    /// Starts playing the review forward from the position shown, or from
+    /// the start once the end has been reached.
+    fn start_playback(&mut self, now: f64) {
+        let interval = self.autoplay_interval.as_secs_f64();
+        let Some(review) = &mut self.review else {
+            return;
+        };
+        let start = if review.index + 1 >= review.positions.len() {
+            0
+        } else {
+            review.index
+        };
+        review.playing = true;
+        review.next_step = now + interval;
+        self.review_step(start);
+    }
+
+    /// Takes the next automatic step of the review if it is due, stopping
+    /// at the last position.
+    fn poll_playback(&mut self, ctx: &egui::Context) {
+        let now = ctx.input(|i| i.time);
+        let interval = self.autoplay_interval.as_secs_f64();
+        let Some(review) = &mut self.review else {
+            return;
+        };
+        if !review.playing {
+            return;
+        }
+        if now >= review.next_step {
+            let index = review.index + 1;
+            review.playing = index + 1 < review.positions.len();
+            review.next_step = now + interval;
+            self.review_step(index);
+        }
+        if let Some(review) = &self.review
+            && review.playing
+        {
+            ctx.request_repaint_after(Duration::from_secs_f64((review.next_step - now).max(0.0)));
+        }
+    }
+
+    /// Shows the position after `ply` moves of the game in the move list,
+    /// starting a review of the current game if none is open.
+    fn jump_to_ply(&mut self, ply: usize) {
+        if self.review.is_none() {
+            self.review = Some(Review {
+                positions: self.board.positions(),
+                index: self.board.history.len(),
+                playing: false,
+                next_step: 0.0,
+            });
+        }
+        self.review_step_manually(ply);
+    }
+
+    /// Takes back the last move. Against the computer its reply is taken
+    /// back too, so that the human is to move again.
+    fn undo_move(&mut self) {
+        if !self.board.undo() {
+            return;
+        }
+        if self.computer_to_move() {
+            self.board.undo();
+        }
+        // Drop any search started on the position before the undo
+        self.new_generation();
+        self.review = None;
+        self.hint = None;
+        self.dragging = None;
+        self.illegal_move = None;
+        self.clock.running = Some(self.board.turn);
+    }
+
+    /// Move list panel: one row per move number with Red's and Black's
+    /// moves. Clicking a move shows the position after it.
+    fn move_list(&mut self, ui: &mut egui::Ui) {
+        // Under review the whole opened game is listed, not just the moves
+        // up to the position shown
+        let (game, current) = match &self.review {
+            Some(review) => (&review.positions[review.positions.len() - 1], review.index),
+            None => (&self.board, self.board.history.len()),
+        };
+        let moves = game.move_log();
+        let start_turn = if moves.len() % 2 == 0 {
+            game.turn
+        } else {
+            game.turn.opposite()
+        };
+        let black_moves = match start_turn {
+            Color::Red => moves.len() / 2,
+            Color::Black => moves.len().div_ceil(2),
+        };
+        let mut number = game.fullmove_number - black_moves as u32;
+
+        let can_jump = self.network.is_none();
+        let mut jump = None;
+        ui.heading("Moves");
+        if ui
+            .add_enabled(
+                self.network.is_none() && !self.board.history.is_empty(),
+                egui::Button::new("Undo"),
+            )
+            .clicked()
+        {
+            self.undo_move();
+        }
+        ui.separator();
+        egui::ScrollArea::vertical()
+            .stick_to_bottom(true)
+            .show(ui, |ui| {
+                if ui
+                    .add_enabled(can_jump, egui::Button::selectable(current == 0, "Start"))
+                    .clicked()
+                {
+                    jump = Some(0);
+                }
+                egui::Grid::new("move_list").striped(true).show(ui, |ui| {
+                    ui.label("");
+                    ui.strong("Red");
+                    ui.strong("Black");
+                    ui.end_row();
+                    let mut turn = start_turn;
+                    if turn == Color::Black {
+                        ui.label(format!("{}.", number));
+                        ui.label("…");
+                    }
+                    for (ply, &(from, to)) in moves.iter().enumerate() {
+                        if turn == Color::Red {
+                            ui.label(format!("{}.", number));
+                        }
+                        let text = notation::move_to_iccs(from, to);
+                        if ui
+                            .add_enabled(
+                                can_jump,
+                                egui::Button::selectable(current == ply + 1, text),
+                            )
+                            .clicked()
+                        {
+                            jump = Some(ply + 1);
+                        }
+                        if turn == Color::Black {
+                            ui.end_row();
+                            number += 1;
+                        }
+                        turn = turn.opposite();
+                    }
+                });
+            });
+        if let Some(ply) = jump {
+            self.jump_to_ply(ply);
+        }
+    }
+
+    fn load_from_file(&mut self) {
+        let text = match std::fs::read_to_string(SAVE_PATH) {
+            Ok(text) => text,
+            Err(err) => {
+                self.status = Some(format!("Failed to read {}: {}", SAVE_PATH, err));
+                return;
+            }
+        };
+        match save::load_game(&text) {
+            Ok(board) => {
+                self.set_board(board);
+                let ui = save::load_ui_state(&text).unwrap_or_default();
+                self.board.selected = ui.selected;
+                self.flipped = ui.flipped;
+            }
+            Err(err) => self.status = Some(format!("Failed to load {}: {}", SAVE_PATH, err)),
+        }
+    }
+
+    /// Plays a move on the board, keeping the clock in step with it.
+    fn play_move(&mut self, from: Pos, to: Pos) -> bool {
+        let mover = self.board.turn;
+        let logged = self.log_moves.then(|| {
+            let record = MoveRecord {
+                fen: self.board.to_fen(),
+                from,
+                to,
+            };
+            (record, ai::evaluate(&self.board))
+        });
+        if !self.board.move_piece(from, to) {
+            return false;
+        }
+        self.hint = None;
+        self.review = None;
+        self.illegal_move = None;
+        if self
+            .network
+            .as_ref()
+            .is_some_and(|network| network.local == mover)
+        {
+            self.send(Message::Move(from, to));
+        }
+        if let Some((record, eval_before)) = logged {
+            let eval_after = ai::evaluate(&self.board);
+            if let Err(err) = movelog::append_move_log_csv(
+                Path::new(MOVE_LOG_PATH),
+                &record,
+                eval_before,
+                eval_after,
+            ) {
+                self.status = Some(format!("Failed to write {}: {}", MOVE_LOG_PATH, err));
+            }
+        }
+        if self.board.state == GameState::Playing {
+            self.clock.switch(mover);
+        } else {
+            self.clock.stop();
+        }
+        true
+    }
+}
+
+impl eframe::App for ChessApp {
+    fn update(&mut self, ctx: &egui::Context, _frame: &mut eframe::Frame) {
+        let title = match self.board.state {
+            GameState::Playing if self.board.is_in_check(self.board.turn) => {
+                format!("Chinese Chess — {:?} to move (check)", self.board.turn)
+            }
+            GameState::Playing => format!("Chinese Chess — {:?} to move", self.board.turn),
+            GameState::Won(winner) => format!("Chinese Chess — {:?} wins", winner),
+            GameState::Draw => "Chinese Chess — Draw".to_string(),
+        };
+        if title != self.title {
+            ctx.send_viewport_cmd(egui::ViewportCommand::Title(title.clone()));
+            self.title = title;
+        }
+
+        if self.board.state == GameState::Playing && !self.paused {
+            let elapsed = Duration::from_secs_f32(ctx.input(|i| i.unstable_dt));
+            if let Some(flagged) = self.clock.tick(elapsed) {
+                self.board.lose_on_time(flagged);
+            }
+            ctx.request_repaint_after(Duration::from_millis(100));
+        }
+
+        self.poll_playback(ctx);
+        self.poll_connecting();
+        self.poll_network();
+        if self.connecting.is_some() || self.network.is_some() {
+            ctx.request_repaint_after(Duration::from_millis(100));
+        }
+
+        self.poll_paste(ctx);
+        self.handle_shortcuts(ctx);
+        self.shortcuts_window(ctx);
+        if self.debug_overlay {
+            self.refresh_debug_analysis();
+        }
+
+        // A finished search waits in its channel until the game resumes.
+        // Only one search runs at a time, and the next starts only once the
+        // last one's move is on the board, so two computer sides take turns
+        // rather than spinning.
+        if !self.paused
+            && !self.drive_computer(ctx)
+            && self.board.state == GameState::Playing
+            && !self.remote_to_move()
+            && !self.confirm_restart
+        {
+            self.handle_keyboard(ctx);
+        }
+
+        if self.confirm_restart {
+            let modal = egui::Modal::new(egui::Id::new("confirm_restart")).show(ctx, |ui| {
+                ui.heading("Restart?");
+                ui.label("Are you sure? The game in progress will be lost.");
+                ui.horizontal(|ui| {
+                    if ui.button("Yes").clicked() {
+                        self.confirm_restart = false;
+                        self.restart();
+                    }
+                    if ui.button("No").clicked() {
+                        self.confirm_restart = false;
+                    }
+                });
+            });
+            // Escape or a click outside the dialog means no
+            if modal.should_close() {
+                self.confirm_restart = false;
+            }
+        }
+
+        egui::SidePanel::right("move_list")
+            .resizable(false)
+            .show(ctx, |ui| self.move_list(ui));
+
+        egui::CentralPanel::default().show(ctx, |ui| {
+            // The controls scroll once the window is too short for them and
+            // a board of MIN_CELL_SIZE squares
+            let controls_height =
+                (ui.available_height() - 11.0 * MIN_CELL_SIZE).max(MIN_CONTROLS_HEIGHT);
+            egui::ScrollArea::vertical()
+                .id_salt("controls")
+                .max_height(controls_height)
+                .show(ui, |ui| {
+                    ui.heading("Chinese Chess");
+                    if let Some(status) = self.status.clone() {
+                        ui.horizontal(|ui| {
+                            ui.colored_label(ui.visuals().error_fg_color, status);
+                            if ui.small_button("Dismiss").clicked() {
+                                self.status = None;
+                            }
+                        });
+                    }
+                    ui.horizontal(|ui| {
+                        for color in [Color::Red, Color::Black] {
+                            let mut text = format!(
+                                "{:?} {}",
+                                color,
+                                clock::format_mm_ss(self.clock.remaining(color))
+                            );
+                            if self.clock.running == Some(color)
+                                && let Some(period) = self.clock.period_remaining()
+                            {
+                                text = format!("{:?} {}", color, clock::format_mm_ss(period));
+                            }
+                            let periods = self.clock.overtime_periods(color);
+                            if periods > 0 {
+                                text.push_str(&format!(" +{} periods", periods));
+                            }
+                            let mut text = egui::RichText::new(text).monospace();
+                            if self.clock.running == Some(color) {
+                                text = text.strong();
+                            }
+                            ui.label(text);
+                        }
+                    });
+                    match self.board.state {
+                        GameState::Playing => {
+                            ui.horizontal(|ui| {
+                                ui.label(format!("Turn: {:?}", self.board.turn));
+                                // The other player's clock and moves can't be held
+                                let label = if self.paused { "Resume" } else { "Pause" };
+                                if ui
+                                    .add_enabled(self.network.is_none(), egui::Button::new(label))
+                                    .clicked()
+                                {
+                                    self.paused = !self.paused;
+                                    self.dragging = None;
+                                }
+                            });
+                            if self.thinking() {
+                                ui.horizontal(|ui| {
+                                    ui.label("Thinking…");
+                                    if ui.button("Move now").clicked() {
+                                        self.stop_search();
+                                    }
+                                });
+                            }
+                            if let Some(err) = self.illegal_move {
+                                ui.label(format!("Illegal move: {}.", err));
+                            }
+                            if let (Some(rules), Some(count)) =
+                                (self.board.counting, self.board.counting_status())
+                            {
+                                ui.label(format!(
+                                    "Counting: {} of {} moves",
+                                    count, rules.move_limit
+                                ));
+                            }
+                            if self.board.is_likely_fortress() {
+                                ui.label("This position is likely a drawn fortress.");
+                            }
+                            let local = self.network.as_ref().map(|network| network.local);
+                            match self.board.draw_offer {
+                                // Our own offer over the network; the answer comes from the other side
+                                Some(offerer) if local == Some(offerer) => {
+                                    ui.label("Draw offered, waiting for a reply…");
+                                }
+                                Some(offerer) => {
+                                    ui.horizontal(|ui| {
+                                        ui.label(format!(
+                                            "{:?} offers a draw. {:?}, do you accept?",
+                                            offerer,
+                                            offerer.opposite()
+                                        ));
+                                        if ui.button("Accept").clicked() {
+                                            self.board.accept_draw(offerer.opposite());
+                                            self.clock.stop();
+                                            self.send(Message::AcceptDraw);
+                                        }
+                                        if ui.button("Decline").clicked() {
+                                            self.board.decline_draw();
+                                            self.send(Message::DeclineDraw);
+                                        }
+                                    });
+                                }
+                                // The computer or the other player decides for themselves
+                                None if self.computer_to_move() || self.remote_to_move() => {}
+                                None => {
+                                    ui.horizontal(|ui| {
+                                        let turn = self.board.turn;
+                                        if ui.button("Resign").clicked() {
+                                            self.board.resign(turn);
+                                            self.clock.stop();
+                                            self.send(Message::Resign);
+                                        }
+                                        if ui.button("Offer Draw").clicked() {
+                                            self.board.offer_draw(turn);
+                                            self.send(Message::OfferDraw);
+                                        }
+                                    });
+                                }
+                            }
+                        }
+                        GameState::Won(_) | GameState::Draw => {
+                            let result = result_text(&self.board);
+                            ui.label(
+                                egui::RichText::new(result)
+                                    .color(egui::Color32::GOLD)
+                                    .size(20.0),
+                            );
+                            if ui.button("Restart").clicked() {
+                                self.request_restart();
+                            }
+                        }
+                    }
+
+                    ui.horizontal(|ui| {
+                        let score = ai::display_score(
+                            ai::evaluate(&self.board),
+                            self.board.turn,
+                            self.eval_perspective,
+                        );
+                        ui.label(format!("Evaluation: {:+}", score));
+                        // Bar fills towards the perspective side, saturating at +/-2000
+                        let fraction = (0.5 + score as f32 / 4000.0).clamp(0.0, 1.0);
+                        ui.add(egui::ProgressBar::new(fraction).desired_width(120.0));
+                        egui::ComboBox::from_id_salt("eval_perspective")
+                            .selected_text(match self.eval_perspective {
+                                EvalPerspective::Red => "Red's view",
+                                EvalPerspective::SideToMove => "Side to move",
+                            })
+                            .show_ui(ui, |ui| {
+                                ui.selectable_value(
+                                    &mut self.eval_perspective,
+                                    EvalPerspective::Red,
+                                    "Red's view",
+                                );
+                                ui.selectable_value(
+                                    &mut self.eval_perspective,
+                                    EvalPerspective::SideToMove,
+                                    "Side to move",
+                                );
+                            });
+                    });
+                    ui.horizontal(|ui| {
+                        if ui
+                            .add_enabled(self.can_hint(), egui::Button::new("Hint"))
+                            .clicked()
+                        {
+                            self.hint = ai::best_move(&self.board, self.board.turn, HINT_DEPTH);
+                        }
+                        if ui.button("Save").clicked() {
+                            self.save_to_file();
+                        }
+                        // Loading would put us out of step with the other player
+                        if ui
+                            .add_enabled(self.network.is_none(), egui::Button::new("Load"))
+                            .clicked()
+                        {
+                            self.load_from_file();
+                        }
+                        if ui.button("Save image").clicked() {
+                            self.save_board_image();
+                        }
+                        ui.add(
+                            egui::TextEdit::singleline(&mut self.image_path)
+                                .desired_width(100.0)
+                                .hint_text("board.png"),
+                        );
+                        if ui.button("Export game").clicked() {
+                            self.export_game();
+                        }
+                        if ui
+                            .add_enabled(self.network.is_none(), egui::Button::new("Open game"))
+                            .clicked()
+                        {
+                            self.open_game();
+                        }
+                    });
+                    if let Some(review) = &self.review {
+                        let (index, last) = (review.index, review.positions.len() - 1);
+                        let playing = review.playing;
+                        ui.horizontal(|ui| {
+                            if ui
+                                .add_enabled(index > 0, egui::Button::new("Prev"))
+                                .clicked()
+                            {
+                                self.review_step_manually(index - 1);
+                            }
+                            ui.label(format!("Move {} of {}", index, last));
+                            if ui
+                                .add_enabled(index < last, egui::Button::new("Next"))
+                                .clicked()
+                            {
+                                self.review_step_manually(index + 1);
+                            }
+                            if playing {
+                                if ui.button("Pause").clicked()
+                                    && let Some(review) = &mut self.review
+                                {
+                                    review.playing = false;
+                                }
+                            } else if ui
+                                .add_enabled(last > 0, egui::Button::new("Play"))
+                                .clicked()
+                            {
+                                self.start_playback(ctx.input(|i| i.time));
+                            }
+                            let mut seconds = self.autoplay_interval.as_secs_f32();
+                            if ui
+                                .add(
+                                    egui::Slider::new(&mut seconds, 0.25..=5.0)
+                                        .suffix(" s per move"),
+                                )
+                                .changed()
+                            {
+                                self.autoplay_interval = Duration::from_secs_f32(seconds);
+                            }
+                            if ui.button("Close").clicked() {
+                                self.review = None;
+                            }
+                        });
+                    }
+                    ui.horizontal(|ui| match &self.network {
+                        Some(network) => {
+                            ui.label(format!("Playing {:?} over the network", network.local));
+                        }
+                        None if self.connecting.is_some() => {
+                            ui.label(format!(
+                                "Waiting for the other player on {}…",
+                                self.net_address
+                            ));
+                        }
+                        None => {
+                            ui.add(
+                                egui::TextEdit::singleline(&mut self.net_address)
+                                    .desired_width(150.0),
+                            );
+                            if ui.button("Host").clicked() {
+                                self.connect(Color::Red);
+                            }
+                            if ui.button("Join").clicked() {
+                                self.connect(Color::Black);
+                            }
+                        }
+                    });
+                    ui.horizontal(|ui| {
+                        ui.label("Computer plays");
+                        for color in [Color::Red, Color::Black] {
+                            ui.checkbox(
+                                &mut self.engine_controls[color as usize],
+                                format!("{:?}", color),
+                            );
+                        }
+                        egui::ComboBox::from_id_salt("difficulty")
+                            .selected_text(format!("{:?}", self.difficulty))
+                            .show_ui(ui, |ui| {
+                                for difficulty in Difficulty::ALL {
+                                    ui.selectable_value(
+                                        &mut self.difficulty,
+                                        difficulty,
+                                        format!("{:?}", difficulty),
+                                    );
+                                }
+                            });
+                    });
+                    ui.horizontal(|ui| {
+                        egui::ComboBox::from_id_salt("variant")
+                            .selected_text(variant_name(self.variant))
+                            .show_ui(ui, |ui| {
+                                for variant in VariantKind::ALL {
+                                    ui.selectable_value(
+                                        &mut self.variant,
+                                        variant,
+                                        variant_name(variant),
+                                    );
+                                }
+                            });
+                        egui::ComboBox::from_id_salt("handicap")
+                            .selected_text(HANDICAPS[self.handicap].0)
+                            .show_ui(ui, |ui| {
+                                for (index, (name, _)) in HANDICAPS.iter().enumerate() {
+                                    ui.selectable_value(&mut self.handicap, index, *name);
+                                }
+                            });
+                        ui.label("given by");
+                        egui::ComboBox::from_id_salt("odds_giver")
+                            .selected_text(format!("{:?}", self.odds_giver))
+                            .show_ui(ui, |ui| {
+                                for color in [Color::Red, Color::Black] {
+                                    ui.selectable_value(
+                                        &mut self.odds_giver,
+                                        color,
+                                        format!("{:?}", color),
+                                    );
+                                }
+                            });
+                        ui.checkbox(&mut self.counting, "Counting rule");
+                        if ui
+                            .add_enabled(self.network.is_none(), egui::Button::new("New game"))
+                            .clicked()
+                        {
+                            self.request_restart();
+                        }
+                    });
+                    ui.horizontal(|ui| {
+                        ui.label("Theme:");
+                        egui::ComboBox::from_id_salt("theme")
+                            .selected_text(self.theme.name)
+                            .show_ui(ui, |ui| {
+                                for theme in Theme::PRESETS {
+                                    ui.selectable_value(&mut self.theme, theme, theme.name);
+                                }
+                            });
+                    });
+                    ui.checkbox(&mut self.flipped, "Black at the bottom");
+                    ui.checkbox(&mut self.show_pins, "Mark pinned pieces");
+                    ui.checkbox(
+                        &mut self.show_attacks,
+                        "Shade points attacked by the side not to move",
+                    );
+                    ui.checkbox(
+                        &mut self.auto_select,
+                        "Auto-select the only defender when in check",
+                    );
+                    ui.checkbox(
+                        &mut self.log_moves,
+                        format!("Log moves with evaluations to {}", MOVE_LOG_PATH),
+                    );
+                    ui.collapsing("Position", |ui| {
+                        ui.horizontal(|ui| {
+                            ui.label("FEN or code:");
+                            ui.text_edit_singleline(&mut self.position);
+                            if ui
+                                .add_enabled(self.network.is_none(), egui::Button::new("Load"))
+                                .clicked()
+                            {
+                                self.load_position();
+                            }
+                            if ui
+                                .add_enabled(self.network.is_none(), egui::Button::new("Paste"))
+                                .on_hover_text("Load a FEN or code from the clipboard")
+                                .clicked()
+                            {
+                                self.awaiting_paste = true;
+                                ctx.send_viewport_cmd(egui::ViewportCommand::RequestPaste);
+                            }
+                        });
+                        ui.horizontal(|ui| {
+                            if ui.button("Copy FEN").clicked() {
+                                ctx.copy_text(self.board.to_fen());
+                            }
+                            if ui.button("Copy code").clicked() {
+                                ctx.copy_text(self.board.to_code());
+                            }
+                        });
+                        if let Some(err) = &self.position_error {
+                            ui.colored_label(ui.visuals().error_fg_color, err);
+                        }
+                    });
+                    ui.collapsing("Puzzle", |ui| {
+                        ui.horizontal(|ui| {
+                            ui.label("FEN:");
+                            ui.text_edit_singleline(&mut self.puzzle.fen);
+                            if ui.button("Load puzzle").clicked() {
+                                self.load_puzzle();
+                            }
+                        });
+                        ui.horizontal(|ui| {
+                            ui.label("Mate in");
+                            ui.add(egui::DragValue::new(&mut self.puzzle.mate_in).range(1..=4));
+                            // The search runs on the UI thread; fine for short mates
+                            if ui.button("Show solution").clicked() {
+                                self.solve_puzzle();
+                            }
+                        });
+                        if let Some(message) = &self.puzzle.message {
+                            ui.label(message);
+                        }
+                    });
+                });
+
+            self.auto_select_defender();
+
+            let available_size = ui.available_size();
+            let cell_size = board_cell_size(available_size);
+            let (response, painter) = ui.allocate_painter(
+                available_size.max(BOARD_CELLS * cell_size),
+                egui::Sense::click_and_drag(),
             );
-            painter.line_segment(
-                [
-                    offset + egui::vec2(5.0 * cell_size, 7.0 * cell_size),
-                    offset + egui::vec2(3.0 * cell_size, 9.0 * cell_size),
-                ],
-                stroke,
+            let offset = board_offset(response.rect, cell_size);
+
+            let theme = self.theme;
+
+            // Background tessellated once and reused until its inputs change
+            self.board_rect = egui::Rect::from_min_max(
+                offset - egui::vec2(cell_size, cell_size),
+                offset + egui::vec2(9.0 * cell_size, 10.0 * cell_size),
             );
+            let key = BackgroundKey {
+                offset,
+                cell_size,
+                theme,
+                pixels_per_point: ctx.pixels_per_point(),
+            };
+            painter.add(egui::Shape::Mesh(self.background_mesh(ctx, key)));
+
+            draw_coordinates(&painter, &theme, offset, cell_size, self.flipped);
+
+            let flipped = self.flipped;
+            let square_center = |pos: Pos| {
+                let pos = view_pos(pos, flipped);
+                offset + egui::vec2(pos.x as f32 * cell_size, pos.y as f32 * cell_size)
+            };
+
+            // Attack heatmap under the pieces, darker for more attackers
+            if self.show_attacks {
+                let attacker = self.board.turn.opposite();
+                for y in 0..HEIGHT {
+                    for x in 0..WIDTH {
+                        let count = self.board.attack_count(Pos::new(x, y), attacker);
+                        if count > 0 {
+                            let center = square_center(Pos::new(x, y));
+                            let strength = (count as f32 * 0.25).min(1.0);
+                            painter.rect_filled(
+                                egui::Rect::from_center_size(
+                                    center,
+                                    egui::vec2(cell_size, cell_size) * 0.9,
+                                ),
+                                0.0,
+                                theme.check.gamma_multiply(strength),
+                            );
+                        }
+                    }
+                }
+            }
 
             // Draw pieces
-            for y in 0..10 {
-                for x in 0..9 {
+            for y in 0..HEIGHT {
+                for x in 0..WIDTH {
                     let pos = Pos::new(x, y);
-                    let center = offset + egui::vec2(x as f32 * cell_size, y as f32 * cell_size);
+                    let center = square_center(pos);
 
                     // Highlight selected
-                    if let Some(selected) = self.board.selected {
-                        if selected == pos {
-                            painter.circle_filled(
-                                center,
-                                cell_size * 0.45,
-                                egui::Color32::from_rgba_premultiplied(0, 255, 0, 100),
-                            );
-                        }
+                    if self.board.selected == Some(pos) {
+                        painter.circle_filled(center, cell_size * 0.45, theme.selection);
+                    }
+
+                    // Keyboard cursor
+                    if self.cursor == pos {
+                        painter.rect_stroke(
+                            egui::Rect::from_center_size(center, egui::vec2(cell_size, cell_size)),
+                            0.0,
+                            egui::Stroke::new(2.0, theme.cursor),
+                            egui::StrokeKind::Inside,
+                        );
                     }
 
                     if let Some(piece) = self.board.get_piece(pos) {
-                        let color = match piece.color {
-                            Color::Red => egui::Color32::RED,
-                            Color::Black => egui::Color32::BLACK,
-                        };
-                        let bg_color = egui::Color32::from_rgb(240, 220, 180);
+                        // A piece being dragged is drawn under the pointer instead
+                        if self.dragging != Some(pos) {
+                            draw_piece(&painter, &theme, center, piece, cell_size);
+                        }
+                    }
+                }
+            }
 
-                        painter.circle_filled(center, cell_size * 0.4, bg_color);
+            if self.show_pins {
+                for color in [Color::Red, Color::Black] {
+                    for pos in self.board.pinned_pieces(color) {
+                        let center = square_center(pos);
                         painter.circle_stroke(
                             center,
-                            cell_size * 0.4,
-                            egui::Stroke::new(2.0, color),
+                            cell_size * 0.46,
+                            egui::Stroke::new(1.5, theme.pinned),
                         );
+                    }
+                }
+            }
 
-                        let text = match (piece.color, piece.piece_type) {
-                            (Color::Red, PieceType::General) => "帥",
-                            (Color::Red, PieceType::Advisor) => "仕",
-                            (Color::Red, PieceType::Elephant) => "相",
-                            (Color::Red, PieceType::Horse) => "傌",
-                            (Color::Red, PieceType::Chariot) => "俥",
-                            (Color::Red, PieceType::Cannon) => "炮",
-                            (Color::Red, PieceType::Soldier) => "兵",
-                            (Color::Black, PieceType::General) => "將",
-                            (Color::Black, PieceType::Advisor) => "士",
-                            (Color::Black, PieceType::Elephant) => "象",
-                            (Color::Black, PieceType::Horse) => "馬",
-                            (Color::Black, PieceType::Chariot) => "車",
-                            (Color::Black, PieceType::Cannon) => "砲",
-                            (Color::Black, PieceType::Soldier) => "卒",
-                        };
+            let human_to_move = !self.paused
+                && !self.confirm_restart
+                && !self.computer_to_move()
+                && !self.remote_to_move();
 
-                        painter.text(
+            // Destinations of the selected piece, or a fainter preview for
+            // an own piece under the pointer while nothing is selected
+            let preview = if self.board.selected.is_none() && self.dragging.is_none() {
+                response
+                    .hover_pos()
+                    .and_then(|pointer_pos| grid_pos(pointer_pos, offset, cell_size, flipped))
+                    .filter(|&pos| {
+                        human_to_move
+                            && self
+                                .board
+                                .get_piece(pos)
+                                .is_some_and(|piece| piece.color == self.board.turn)
+                    })
+            } else {
+                None
+            };
+            if let Some(from) = self.board.selected.or(preview) {
+                for to in self.board.legal_moves_from(from) {
+                    let center = square_center(to);
+                    if preview.is_some() {
+                        painter.circle_stroke(
                             center,
-                            egui::Align2::CENTER_CENTER,
-                            text,
-                            egui::FontId::proportional(cell_size * 0.5),
-                            color,
+                            cell_size * 0.12,
+                            egui::Stroke::new(1.5, theme.selection.gamma_multiply(0.6)),
                         );
+                    } else {
+                        painter.circle_filled(center, cell_size * 0.12, theme.selection);
                     }
                 }
             }
 
+            // Pulsing halo on a General in check
+            if self.board.state == GameState::Playing
+                && self.board.is_in_check(self.board.turn)
+                && let Some(general) = self.board.find_general(self.board.turn)
+            {
+                let time = ctx.input(|i| i.time);
+                let pulse = 0.5 + 0.5 * (time * std::f64::consts::TAU).sin() as f32;
+                let center = square_center(general);
+                painter.circle_stroke(
+                    center,
+                    cell_size * 0.46,
+                    egui::Stroke::new(
+                        cell_size * 0.08,
+                        theme.check.gamma_multiply(0.3 + 0.7 * pulse),
+                    ),
+                );
+                ctx.request_repaint();
+            }
+
+            // Fading outline on a square clicked out of turn
+            if let Some((pos, clicked_at)) = self.flash {
+                let fade = 1.0 - (ctx.input(|i| i.time) - clicked_at) / FLASH_SECONDS;
+                if fade > 0.0 {
+                    let center = square_center(pos);
+                    painter.rect_stroke(
+                        egui::Rect::from_center_size(center, egui::vec2(cell_size, cell_size)),
+                        0.0,
+                        egui::Stroke::new(3.0, theme.check.gamma_multiply(fade as f32)),
+                        egui::StrokeKind::Inside,
+                    );
+                    ctx.request_repaint();
+                } else {
+                    self.flash = None;
+                }
+            }
+
+            // Move arrows are drawn over the pieces
+            if let Some(last) = self.board.history.last() {
+                draw_move_arrow(
+                    &painter,
+                    square_center(last.from),
+                    square_center(last.to),
+                    theme.last_move,
+                    cell_size,
+                );
+            }
+            if let Some((from, to)) = self.hint {
+                draw_move_arrow(
+                    &painter,
+                    square_center(from),
+                    square_center(to),
+                    theme.hint,
+                    cell_size,
+                );
+            }
+
+            // Dragged piece follows the pointer
+            if let Some(from) = self.dragging
+                && let Some(piece) = self.board.get_piece(from)
+                && let Some(pointer_pos) = response.interact_pointer_pos()
+            {
+                draw_piece(&painter, &theme, pointer_pos, piece, cell_size);
+            }
+
+            if self.debug_overlay
+                && let Some(analysis) = &self.debug_analysis
+            {
+                let best = analysis.best.map_or("-".to_string(), |(from, to)| {
+                    notation::move_to_iccs(from, to)
+                });
+                let text = format!(
+                    "eval  {:+}\nbest  {}\ndepth {}\nnodes {}\ntime  {} ms",
+                    analysis.score,
+                    best,
+                    DEBUG_DEPTH,
+                    analysis.stats.nodes_searched,
+                    analysis.stats.elapsed.as_millis()
+                );
+                let galley = painter.layout_no_wrap(
+                    text,
+                    egui::FontId::monospace(12.0),
+                    egui::Color32::WHITE,
+                );
+                let corner = self.board_rect.left_top() + egui::vec2(4.0, 4.0);
+                let background =
+                    egui::Rect::from_min_size(corner, galley.size() + egui::vec2(8.0, 8.0));
+                painter.rect_filled(background, 2.0, egui::Color32::from_black_alpha(170));
+                painter.galley(corner + egui::vec2(4.0, 4.0), galley, egui::Color32::WHITE);
+            }
+
+            if self.paused {
+                let board_rect =
+                    egui::Rect::from_min_size(offset, egui::vec2(8.0 * cell_size, 9.0 * cell_size));
+                painter.rect_filled(
+                    board_rect.expand(cell_size * 0.5),
+                    0.0,
+                    egui::Color32::from_black_alpha(140),
+                );
+                painter.text(
+                    board_rect.center(),
+                    egui::Align2::CENTER_CENTER,
+                    "Paused",
+                    egui::FontId::proportional(cell_size),
+                    egui::Color32::WHITE,
+                );
+            }
+
             // Handle input
-            if response.clicked() {
-                if let Some(pointer_pos) = response.interact_pointer_pos() {
-                    let relative_pos = pointer_pos - offset;
-                    // Round to nearest grid point
-                    let x = (relative_pos.x / cell_size).round() as i32;
-                    let y = (relative_pos.y / cell_size).round() as i32;
-
-                    if x >= 0 && x < 9 && y >= 0 && y < 10 {
-                        let clicked_pos = Pos::new(x as usize, y as usize);
-
-                        if let Some(selected) = self.board.selected {
-                            if self.board.move_piece(selected, clicked_pos) {
-                                self.board.selected = None;
-                            } else {
-                                if let Some(piece) = self.board.get_piece(clicked_pos) {
-                                    if piece.color == self.board.turn {
-                                        self.board.selected = Some(clicked_pos);
-                                    } else {
-                                        self.board.selected = None;
-                                    }
-                                } else {
-                                    self.board.selected = None;
-                                }
-                            }
-                        } else {
-                            if let Some(piece) = self.board.get_piece(clicked_pos) {
-                                if piece.color == self.board.turn {
-                                    self.board.selected = Some(clicked_pos);
-                                }
-                            }
-                        }
+            if human_to_move
+                && response.drag_started()
+                && let Some(pointer_pos) = response.interact_pointer_pos()
+                && let Some(pos) = grid_pos(pointer_pos, offset, cell_size, flipped)
+                && let Some(piece) = self.board.get_piece(pos)
+                && piece.color == self.board.turn
+            {
+                self.dragging = Some(pos);
+                self.board.selected = Some(pos);
+            }
+
+            if response.drag_stopped()
+                && let Some(from) = self.dragging.take()
+            {
+                // Off-board or illegal drops snap back, leaving the piece selected
+                if let Some(pointer_pos) = response.interact_pointer_pos()
+                    && let Some(to) = grid_pos(pointer_pos, offset, cell_size, flipped)
+                    && to != from
+                {
+                    if self.play_move(from, to) {
+                        self.board.selected = None;
+                    } else {
+                        self.illegal_move = self.board.explain_move(from, to).err();
                     }
                 }
             }
+
+            // Right-click anywhere cancels: selection, a drag in progress
+            // and the hint. Sense::click covers the secondary button too.
+            if response.secondary_clicked() {
+                self.board.selected = None;
+                self.dragging = None;
+                self.hint = None;
+            }
+
+            if response.clicked()
+                && let Some(pointer_pos) = response.interact_pointer_pos()
+                && let Some(clicked_pos) = grid_pos(pointer_pos, offset, cell_size, flipped)
+            {
+                if self.is_out_of_turn_click(clicked_pos) {
+                    self.flash = Some((clicked_pos, ctx.input(|i| i.time)));
+                } else if human_to_move {
+                    self.activate(clicked_pos);
+                }
+            }
+        });
+    }
+}
+
+/// How a finished game ended, for the banner under the board.
+fn result_text(board: &Board) -> String {
+    match board.state {
+        GameState::Won(winner) => match board.result_reason {
+            Some(WinReason::Checkmate) => format!("{:?} wins by checkmate!", winner),
+            Some(WinReason::Stalemate) => format!("{:?} wins: no legal move left.", winner),
+            Some(WinReason::Resignation) => format!("{:?} wins by resignation.", winner),
+            Some(WinReason::Timeout) => format!("{:?} wins on time.", winner),
+            None => format!("{:?} Wins!", winner),
+        },
+        _ if board.is_insufficient_material() => "Draw: neither side can give mate.".to_string(),
+        _ if board.is_draw_by_counting() => "Draw by the counting rule.".to_string(),
+        _ => "Draw agreed.".to_string(),
+    }
+}
+
+fn variant_name(variant: VariantKind) -> &'static str {
+    match variant {
+        VariantKind::Standard => "Standard",
+        VariantKind::SymmetricRandom => "Symmetric random",
+    }
+}
+
+/// Size of the board with its labels, in squares: the grid spans 8 by 9
+/// and the labels take another half square or so on each side.
+const BOARD_CELLS: egui::Vec2 = egui::vec2(10.0, 11.0);
+
+/// Largest square size at which the board fits `available` without
+/// distortion, but no smaller than `MIN_CELL_SIZE`.
+fn board_cell_size(available: egui::Vec2) -> f32 {
+    (available / BOARD_CELLS).min_elem().max(MIN_CELL_SIZE)
+}
+
+/// Screen position of the top-left grid point (a9) for a board centred in
+/// `area`. Drawing and [`grid_pos`] both go through this, so clicks land on
+/// the squares as drawn at any size.
+fn board_offset(area: egui::Rect, cell_size: f32) -> egui::Pos2 {
+    // The grid point a9 sits one square in from the labelled board's corner
+    let corner = area.center() - BOARD_CELLS * cell_size / 2.0;
+    corner + egui::vec2(cell_size, cell_size)
+}
+
+/// Maps a screen position to the nearest grid point, if it lies on the board.
+fn grid_pos(
+    pointer_pos: egui::Pos2,
+    offset: egui::Pos2,
+    cell_size: f32,
+    flipped: bool,
+) -> Option<Pos> {
+    let relative_pos = pointer_pos - offset;
+    // Round to nearest grid point
+    let x = (relative_pos.x / cell_size).round() as i32;
+    let y = (relative_pos.y / cell_size).round() as i32;
+
+    Pos::try_new(x, y).map(|pos| view_pos(pos, flipped))
+}
+
+/// Where `pos` is drawn, counted from the top-left grid point: itself, or
+/// turned half a circle when the board is flipped. Its own inverse, so it
+/// also maps drawn points back to the board.
+fn view_pos(pos: Pos, flipped: bool) -> Pos {
+    if flipped {
+        Pos::new(WIDTH - 1 - pos.x, HEIGHT - 1 - pos.y)
+    } else {
+        pos
+    }
+}
+
+/// The board's fills, grid, river edges and palace diagonals, for
+/// [`BackgroundCache`]. Labels are drawn separately by
+/// [`draw_coordinates`].
+fn board_background(theme: &Theme, offset: egui::Pos2, cell_size: f32) -> Vec<egui::Shape> {
+    let point = |x: f32, y: f32| offset + egui::vec2(x * cell_size, y * cell_size);
+    let stroke = egui::Stroke::new(1.0, theme.line);
+    let mut shapes = Vec::new();
+
+    // Board and river fills, reaching out far enough to cover the labels
+    shapes.push(egui::Shape::rect_filled(
+        egui::Rect::from_min_max(point(-1.0, -1.0), point(9.0, 10.0)),
+        0.0,
+        theme.background,
+    ));
+    shapes.push(egui::Shape::rect_filled(
+        egui::Rect::from_min_max(point(0.0, 4.0), point(8.0, 5.0)),
+        0.0,
+        theme.river,
+    ));
+
+    // Horizontal lines
+    for y in 0..HEIGHT {
+        let y = y as f32;
+        shapes.push(egui::Shape::line_segment(
+            [point(0.0, y), point(8.0, y)],
+            stroke,
+        ));
+    }
+
+    // Vertical lines, broken by the river except at the edges
+    for x in 0..WIDTH {
+        let x = x as f32;
+        shapes.push(egui::Shape::line_segment(
+            [point(x, 0.0), point(x, 4.0)],
+            stroke,
+        ));
+        shapes.push(egui::Shape::line_segment(
+            [point(x, 5.0), point(x, 9.0)],
+            stroke,
+        ));
+    }
+    for x in [0.0, 8.0] {
+        shapes.push(egui::Shape::line_segment(
+            [point(x, 4.0), point(x, 5.0)],
+            stroke,
+        ));
+    }
+
+    // Palace diagonals, Black's at the top and Red's at the bottom
+    for top in [0.0, 7.0] {
+        shapes.push(egui::Shape::line_segment(
+            [point(3.0, top), point(5.0, top + 2.0)],
+            stroke,
+        ));
+        shapes.push(egui::Shape::line_segment(
+            [point(5.0, top), point(3.0, top + 2.0)],
+            stroke,
+        ));
+    }
+    shapes
+}
+
+/// Draws the file numbers, rank numbers and river text around the grid.
+/// Files are numbered from each side's own right: Red's in Chinese
+/// numerals along the bottom, Black's in Arabic numerals along the top.
+/// Ranks use the ICCS digits, counted up from Red's back rank. A flipped
+/// board turns the labels with it, so Black's files run along the bottom.
+fn draw_coordinates(
+    painter: &egui::Painter,
+    theme: &Theme,
+    offset: egui::Pos2,
+    cell_size: f32,
+    flipped: bool,
+) {
+    const RED_FILES: [&str; 9] = ["九", "八", "七", "六", "五", "四", "三", "二", "一"];
+    let font = egui::FontId::proportional(cell_size * 0.3);
+    let label_color = theme.label;
+    let margin = cell_size * 0.7;
+
+    let (top, bottom) = if flipped {
+        (9.0 * cell_size + margin, -margin)
+    } else {
+        (-margin, 9.0 * cell_size + margin)
+    };
+    for (x, red_file) in RED_FILES.iter().enumerate() {
+        let column = view_pos(Pos::new(x, 0), flipped).x as f32 * cell_size;
+        painter.text(
+            offset + egui::vec2(column, top),
+            egui::Align2::CENTER_CENTER,
+            (x + 1).to_string(),
+            font.clone(),
+            label_color,
+        );
+        painter.text(
+            offset + egui::vec2(column, bottom),
+            egui::Align2::CENTER_CENTER,
+            *red_file,
+            font.clone(),
+            label_color,
+        );
+    }
+
+    for y in 0..HEIGHT {
+        painter.text(
+            offset
+                + egui::vec2(
+                    -margin,
+                    view_pos(Pos::new(0, y), flipped).y as f32 * cell_size,
+                ),
+            egui::Align2::CENTER_CENTER,
+            (9 - y).to_string(),
+            font.clone(),
+            label_color,
+        );
+    }
+
+    // River text, centred in the band on each half of the board
+    let river_font = egui::FontId::proportional(cell_size * 0.5);
+    for (column, text) in [(2.0, "楚 河"), (6.0, "漢 界")] {
+        painter.text(
+            offset + egui::vec2(column * cell_size, 4.5 * cell_size),
+            egui::Align2::CENTER_CENTER,
+            text,
+            river_font.clone(),
+            theme.line,
+        );
+    }
+}
+
+/// Draws an arrow from `from` to `to`, sized relative to `cell_size`.
+///
+/// Visual check: from the start position, a hint or last move of h2e2
+/// (cannon, straight) and h0g2 (horse, diagonal) should both show a
+/// symmetric head pointing at the destination.
+fn draw_move_arrow(
+    painter: &egui::Painter,
+    from: egui::Pos2,
+    to: egui::Pos2,
+    color: egui::Color32,
+    cell_size: f32,
+) {
+    if from == to {
+        return;
+    }
+    let stroke = egui::Stroke::new(cell_size * 0.08, color);
+    painter.line_segment([from, to], stroke);
+    for point in arrowhead_points(from, to, cell_size * 0.3) {
+        painter.line_segment([to, point], stroke);
+    }
+}
+
+/// End points of the two short segments forming an arrowhead at `to`,
+/// each `size` back along the shaft and spread to either side of it.
+fn arrowhead_points(from: egui::Pos2, to: egui::Pos2, size: f32) -> [egui::Pos2; 2] {
+    let direction = (to - from).normalized();
+    let back = to - direction * size;
+    let spread = egui::vec2(-direction.y, direction.x) * size * 0.5;
+    [back + spread, back - spread]
+}
+
+fn draw_piece(
+    painter: &egui::Painter,
+    theme: &Theme,
+    center: egui::Pos2,
+    piece: Piece,
+    cell_size: f32,
+) {
+    let color = match piece.color {
+        Color::Red => theme.red_piece,
+        Color::Black => theme.black_piece,
+    };
+
+    painter.circle_filled(center, cell_size * 0.4, theme.piece_fill);
+    painter.circle_stroke(center, cell_size * 0.4, egui::Stroke::new(2.0, color));
+
+    painter.text(
+        center,
+        egui::Align2::CENTER_CENTER,
+        piece.glyph(),
+        egui::FontId::proportional(cell_size * 0.5),
+        color,
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Input for one frame in which `key` is pressed and let go, so the
+    /// next press isn't taken for a repeat.
+    fn key_press(key: egui::Key) -> egui::RawInput {
+        let event = |pressed| egui::Event::Key {
+            key,
+            physical_key: None,
+            pressed,
+            repeat: false,
+            modifiers: egui::Modifiers::NONE,
+        };
+        egui::RawInput {
+            events: vec![event(true), event(false)],
+            ..Default::default()
+        }
+    }
+
+    /// Runs one frame in which `key` is pressed, handing the input to the
+    /// keyboard handler.
+    fn press(app: &mut ChessApp, ctx: &egui::Context, key: egui::Key) {
+        let _ = ctx.run(key_press(key), |ctx| app.handle_keyboard(ctx));
+    }
+
+    #[test]
+    fn keyboard_cursor_stays_on_the_board() {
+        let ctx = egui::Context::default();
+        let mut app = ChessApp::new();
+        press(&mut app, &ctx, egui::Key::ArrowDown);
+        assert_eq!(app.cursor, Pos::new(4, 9));
+        for _ in 0..12 {
+            press(&mut app, &ctx, egui::Key::ArrowLeft);
+            press(&mut app, &ctx, egui::Key::ArrowUp);
+        }
+        assert_eq!(app.cursor, Pos::new(0, 0));
+        for _ in 0..12 {
+            press(&mut app, &ctx, egui::Key::ArrowRight);
+            press(&mut app, &ctx, egui::Key::ArrowDown);
+        }
+        assert_eq!(app.cursor, Pos::new(WIDTH - 1, HEIGHT - 1));
+    }
+
+    #[test]
+    fn enter_selects_then_moves() {
+        let ctx = egui::Context::default();
+        let mut app = ChessApp::new();
+        app.cursor = Pos::new(7, 7);
+        press(&mut app, &ctx, egui::Key::Enter);
+        assert_eq!(app.board.selected, Some(Pos::new(7, 7)));
+        for _ in 0..3 {
+            press(&mut app, &ctx, egui::Key::ArrowLeft);
+        }
+        press(&mut app, &ctx, egui::Key::Space);
+        assert_eq!(app.board.move_log(), vec![(Pos::new(7, 7), Pos::new(4, 7))]);
+        assert_eq!(app.board.selected, None);
+    }
+
+    #[test]
+    fn escape_clears_the_selection() {
+        let ctx = egui::Context::default();
+        let mut app = ChessApp::new();
+        app.cursor = Pos::new(7, 7);
+        press(&mut app, &ctx, egui::Key::Enter);
+        press(&mut app, &ctx, egui::Key::Escape);
+        assert_eq!(app.board.selected, None);
+    }
+
+    #[test]
+    fn escape_cancels_an_auto_selection_for_good() {
+        let ctx = egui::Context::default();
+        let mut app = ChessApp::new();
+        app.auto_select = true;
+        // Only Black's Chariot, by taking the checker, can answer the check
+        app.set_board(Board::from_fen("3k5/9/9/9/r2R5/9/9/9/9/4K4 b").unwrap());
+        app.auto_select_defender();
+        assert_eq!(app.board.selected, Some(Pos::new(0, 4)));
+        press(&mut app, &ctx, egui::Key::Escape);
+        app.auto_select_defender();
+        assert_eq!(app.board.selected, None);
+    }
+
+    #[test]
+    fn auto_select_waits_for_a_human_to_move() {
+        let mut app = ChessApp::new();
+        app.auto_select = true;
+        app.set_board(Board::from_fen("3k5/9/9/9/r2R5/9/9/9/9/4K4 b").unwrap());
+        app.engine_controls = [false, true];
+        app.auto_select_defender();
+        assert_eq!(app.board.selected, None);
+        app.engine_controls = [false; 2];
+        app.paused = true;
+        app.auto_select_defender();
+        assert_eq!(app.board.selected, None);
+        app.paused = false;
+        app.auto_select_defender();
+        assert_eq!(app.board.selected, Some(Pos::new(0, 4)));
+    }
+
+    #[test]
+    fn result_text_names_the_counting_rule() {
+        let mut board = Board::from_fen("5k3/9/9/9/9/9/9/4R4/9/3K5 w").unwrap();
+        board.counting = Some(CountingRules {
+            material_threshold: 2,
+            move_limit: 1,
+        });
+        let (from, to) = notation::iccs_to_move("e2e3").unwrap();
+        board.try_move(from, to).unwrap();
+        assert_eq!(board.state, GameState::Draw);
+        assert_eq!(result_text(&board), "Draw by the counting rule.");
+
+        let mut agreed = Board::new();
+        agreed.offer_draw(Color::Red);
+        agreed.accept_draw(Color::Black);
+        assert_eq!(result_text(&agreed), "Draw agreed.");
+    }
+
+    #[test]
+    fn new_board_stops_the_running_search() {
+        let mut app = ChessApp::new();
+        app.start_computer_move();
+        let stop = app.search.as_ref().unwrap().stop.clone();
+        assert!(!stop.load(Ordering::Relaxed));
+        app.set_board(Board::new());
+        assert!(stop.load(Ordering::Relaxed));
+        assert!(!app.thinking());
+
+        // A stopped search still answers, with a legal move
+        let result = app.search.as_ref().unwrap().receiver.recv().unwrap();
+        let (from, to) = result.unwrap();
+        assert!(app.board.legal_moves_from(from).contains(&to));
+    }
+
+    #[test]
+    fn hint_is_a_legal_move_for_the_side_to_move() {
+        let mut app = ChessApp::new();
+        for _ in 0..4 {
+            app.perform(Action::Hint);
+            let (from, to) = app.hint.expect("a hint in a playable position");
+            let piece = app.board.get_piece(from).unwrap();
+            assert_eq!(piece.color, app.board.turn);
+            assert!(app.board.legal_moves_from(from).contains(&to));
+            // Playing it clears the hint for the other side
+            app.activate(from);
+            app.activate(to);
+            assert_eq!(app.hint, None);
+        }
+
+        // No hint while the computer is to move
+        app.engine_controls[app.board.turn as usize] = true;
+        app.perform(Action::Hint);
+        assert_eq!(app.hint, None);
+    }
+
+    #[test]
+    fn arrowhead_straight_and_diagonal() {
+        // Straight right: both points 10 back, 5 to either side
+        let [a, b] = arrowhead_points(egui::pos2(0.0, 0.0), egui::pos2(100.0, 0.0), 10.0);
+        assert!((a - egui::pos2(90.0, 5.0)).length() < 1e-4, "{:?}", a);
+        assert!((b - egui::pos2(90.0, -5.0)).length() < 1e-4, "{:?}", b);
+
+        // A Horse move: symmetric about the shaft, at the same distance
+        let (from, to) = (egui::pos2(0.0, 0.0), egui::pos2(30.0, 60.0));
+        let [a, b] = arrowhead_points(from, to, 10.0);
+        let midpoint = a + (b - a) / 2.0;
+        let shaft = (to - from).normalized();
+        assert!(((to - midpoint).length() - 10.0).abs() < 1e-4);
+        assert!((to - midpoint).normalized().dot(shaft) > 0.9999);
+        assert!((b - a).dot(shaft).abs() < 1e-4);
+        assert!(((b - a).length() - 10.0).abs() < 1e-4);
+    }
+
+    /// Two ends of a network game on a free local port: this app's and
+    /// the other player's.
+    fn connected_pair() -> (Connection, Connection) {
+        let addr = std::net::TcpListener::bind("127.0.0.1:0")
+            .unwrap()
+            .local_addr()
+            .unwrap();
+        let host = thread::spawn(move || net::host(addr).unwrap());
+        // Keep trying until the host is listening
+        let deadline = std::time::Instant::now() + Duration::from_secs(5);
+        let joined = loop {
+            match net::join(addr) {
+                Ok(connection) => break connection,
+                Err(err) if std::time::Instant::now() > deadline => panic!("{}", err),
+                Err(_) => thread::sleep(Duration::from_millis(10)),
+            }
+        };
+        (host.join().unwrap(), joined)
+    }
+
+    #[test]
+    fn illegal_network_moves_are_ignored() {
+        let (connection, mut remote) = connected_pair();
+        let mut app = ChessApp::new();
+        app.network = Some(NetworkGame {
+            connection,
+            local: Color::Black,
         });
+        let play = |iccs: &str| {
+            let (from, to) = notation::iccs_to_move(iccs).unwrap();
+            Message::Move(from, to)
+        };
+        // Blocked Chariot, a Black move out of turn, then a real move
+        for message in [play("a0a5"), play("h9g7"), play("h2e2")] {
+            remote.send(message).unwrap();
+        }
+
+        let deadline = std::time::Instant::now() + Duration::from_secs(5);
+        while app.board.history.is_empty() && std::time::Instant::now() < deadline {
+            app.poll_network();
+            thread::sleep(Duration::from_millis(5));
+        }
+        app.poll_network();
+        assert_eq!(
+            app.board.move_log(),
+            vec![notation::iccs_to_move("h2e2").unwrap()]
+        );
+        assert!(app.board.validate().is_ok());
+        assert!(app.network.is_some());
+        assert_eq!(
+            app.status.as_deref(),
+            Some("Ignored invalid move h9g7 from the other player")
+        );
+    }
+
+    #[test]
+    fn save_image_writes_the_board_or_says_why_not() {
+        let path = std::env::temp_dir().join(format!("board-{}.png", std::process::id()));
+        let mut app = ChessApp::new();
+        app.image_path = path.to_string_lossy().into_owned();
+        app.save_board_image();
+        assert_eq!(app.status, None);
+        let image = image::open(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!((image.width(), image.height()), (480, 530));
+
+        app.image_path = std::env::temp_dir()
+            .join("no such directory")
+            .join("board.png")
+            .to_string_lossy()
+            .into_owned();
+        app.save_board_image();
+        assert!(app.status.unwrap().starts_with("Failed to write"));
+    }
+
+    #[test]
+    fn typed_positions_load_only_when_playable() {
+        let mut app = ChessApp::new();
+        app.position =
+            "rnbakabnr/9/1c5c1/p1p1p1p1p/9/9/P1P1P1P1P/1C5C1/9/RNBAKABNR w - - 0 1".into();
+        app.load_position();
+        assert_eq!(app.board.snapshot(), Board::new().snapshot());
+        assert_eq!(app.position_error, None);
+
+        // Bad text and a position already mated leave the board alone
+        let (from, to) = notation::iccs_to_move("h2e2").unwrap();
+        app.play_move(from, to);
+        let before = app.board.snapshot();
+        for text in ["rnbakabnr/9/9 w", "3k5/3R5/3R5/9/9/9/9/9/9/4K4 b"] {
+            app.position = text.into();
+            app.load_position();
+            assert!(app.position_error.is_some(), "{}", text);
+            assert_eq!(app.board.snapshot(), before, "{}", text);
+        }
+    }
+
+    #[test]
+    fn playback_steps_on_time_and_yields_to_manual_steps() {
+        let mut app = ChessApp::new();
+        for iccs in ["h2e2", "h9g7", "h0g2"] {
+            let (from, to) = notation::iccs_to_move(iccs).unwrap();
+            app.play_move(from, to);
+        }
+        app.autoplay_interval = Duration::from_secs(1);
+        let ctx = egui::Context::default();
+        let frame = |app: &mut ChessApp, time: f64| {
+            let input = egui::RawInput {
+                time: Some(time),
+                ..Default::default()
+            };
+            let _ = ctx.run(input, |ctx| app.poll_playback(ctx));
+            app.review
+                .as_ref()
+                .map(|review| (review.index, review.playing))
+        };
+
+        app.jump_to_ply(0);
+        app.start_playback(0.0);
+        assert_eq!(frame(&mut app, 0.5), Some((0, true)));
+        assert_eq!(frame(&mut app, 1.0), Some((1, true)));
+
+        // A step by hand stops playback where it is put
+        app.review_step_manually(0);
+        assert_eq!(frame(&mut app, 5.0), Some((0, false)));
+
+        app.start_playback(5.0);
+        assert_eq!(frame(&mut app, 6.0), Some((1, true)));
+        assert_eq!(frame(&mut app, 7.0), Some((2, true)));
+        assert_eq!(frame(&mut app, 8.0), Some((3, false)));
+        assert_eq!(frame(&mut app, 9.0), Some((3, false)));
+        assert_eq!(
+            app.board.to_fen(),
+            app.review.as_ref().unwrap().positions[3].to_fen()
+        );
+    }
+
+    #[test]
+    fn computer_sides_start_one_search_per_turn() {
+        let mut app = ChessApp::new();
+        app.engine_controls = [true; 2];
+        app.difficulty = Difficulty::Easy;
+        let ctx = egui::Context::default();
+
+        // Every search is told apart by its stop flag
+        let mut searches: Vec<(usize, Arc<AtomicBool>)> = Vec::new();
+        let deadline = std::time::Instant::now() + Duration::from_secs(30);
+        while app.board.history.len() < 4 && std::time::Instant::now() < deadline {
+            assert!(app.drive_computer(&ctx));
+            let search = app
+                .search
+                .as_ref()
+                .expect("a search runs on the computer's turn");
+            match searches.last() {
+                Some((_, stop)) if Arc::ptr_eq(stop, &search.stop) => {}
+                _ => searches.push((app.board.history.len(), search.stop.clone())),
+            }
+            thread::sleep(Duration::from_millis(1));
+        }
+        let turns: Vec<usize> = searches.iter().map(|&(turn, _)| turn).collect();
+        assert_eq!(turns, [0, 1, 2, 3, 4]);
+
+        // A human side gets none
+        app.engine_controls = [false; 2];
+        let before = app.board.history.len();
+        app.search = None;
+        assert!(!app.drive_computer(&ctx));
+        assert!(app.search.is_none());
+        assert_eq!(app.board.history.len(), before);
+    }
+
+    #[test]
+    fn only_unselected_clicks_on_the_waiting_side_flash() {
+        let mut app = ChessApp::new();
+        let (e0, e9, e4) = (Pos::new(4, 9), Pos::new(4, 0), Pos::new(4, 5));
+        // Red to move: Black's pieces flash, Red's and empty squares don't
+        assert!(app.is_out_of_turn_click(e9));
+        assert!(!app.is_out_of_turn_click(e0));
+        assert!(!app.is_out_of_turn_click(e4));
+
+        // With a piece selected, an enemy piece is a capture attempt
+        app.board.selected = Some(e0);
+        assert!(!app.is_out_of_turn_click(e9));
+        app.board.selected = None;
+
+        app.paused = true;
+        assert!(!app.is_out_of_turn_click(e9));
+        app.paused = false;
+        app.board.resign(Color::Red);
+        assert!(!app.is_out_of_turn_click(e9));
+    }
+
+    #[test]
+    fn clicks_find_the_drawn_squares_at_any_size() {
+        for size in [
+            egui::vec2(100.0, 100.0),
+            egui::vec2(600.0, 560.0),
+            egui::vec2(3000.0, 400.0),
+            egui::vec2(400.0, 3000.0),
+        ] {
+            let area = egui::Rect::from_min_size(egui::pos2(20.0, 50.0), size);
+            let cell_size = board_cell_size(size);
+            assert!(cell_size >= MIN_CELL_SIZE);
+            let offset = board_offset(area, cell_size);
+            // Centred, and inside the area whenever the area is big enough
+            let board = egui::Rect::from_center_size(area.center(), BOARD_CELLS * cell_size);
+            assert!((offset - (board.min + egui::vec2(cell_size, cell_size))).length() < 1e-3);
+            if cell_size > MIN_CELL_SIZE {
+                assert!(area.expand(1e-3).contains_rect(board), "{:?}", size);
+            }
+
+            for flipped in [false, true] {
+                for y in 0..HEIGHT {
+                    for x in 0..WIDTH {
+                        let pos = Pos::new(x, y);
+                        let drawn = view_pos(pos, flipped);
+                        let center = offset
+                            + egui::vec2(drawn.x as f32 * cell_size, drawn.y as f32 * cell_size);
+                        let near = center + egui::vec2(0.4, -0.4) * cell_size;
+                        assert_eq!(grid_pos(center, offset, cell_size, flipped), Some(pos));
+                        assert_eq!(grid_pos(near, offset, cell_size, flipped), Some(pos));
+                    }
+                }
+                let outside = offset - egui::vec2(cell_size, 0.0);
+                assert_eq!(grid_pos(outside, offset, cell_size, flipped), None);
+            }
+        }
+    }
+
+    #[test]
+    fn debug_analysis_runs_once_per_position() {
+        let mut app = ChessApp::new();
+        // A score no analysis gives marks the cached entry
+        let mark = |app: &mut ChessApp| app.debug_analysis.as_mut().unwrap().score = i32::MIN;
+        let marked = |app: &ChessApp| app.debug_analysis.as_ref().unwrap().score == i32::MIN;
+
+        app.refresh_debug_analysis();
+        assert_eq!(
+            app.debug_analysis.as_ref().unwrap().key,
+            app.board.zobrist_hash()
+        );
+        mark(&mut app);
+        app.refresh_debug_analysis();
+        app.refresh_debug_analysis();
+        assert!(marked(&app));
+
+        let (from, to) = notation::iccs_to_move("h2e2").unwrap();
+        app.play_move(from, to);
+        app.refresh_debug_analysis();
+        assert!(!marked(&app));
+        assert_eq!(
+            app.debug_analysis.as_ref().unwrap().key,
+            app.board.zobrist_hash()
+        );
+        mark(&mut app);
+        app.refresh_debug_analysis();
+        assert!(marked(&app));
+    }
+
+    #[test]
+    fn shortcuts_follow_their_bindings() {
+        let mut app = ChessApp::new();
+        let ctx = egui::Context::default();
+        let shortcut = |app: &mut ChessApp, key| {
+            let _ = ctx.run(key_press(key), |ctx| app.handle_shortcuts(ctx));
+        };
+        shortcut(&mut app, egui::Key::P);
+        assert!(app.paused);
+        shortcut(&mut app, egui::Key::P);
+        assert!(!app.paused);
+
+        // Rebinding takes a key press, and reserved keys are passed over
+        app.rebinding = Some(Action::Pause);
+        shortcut(&mut app, egui::Key::Enter);
+        assert_eq!(app.rebinding, Some(Action::Pause));
+        shortcut(&mut app, egui::Key::K);
+        assert_eq!(app.rebinding, None);
+        shortcut(&mut app, egui::Key::P);
+        assert!(!app.paused);
+        shortcut(&mut app, egui::Key::K);
+        assert!(app.paused);
+    }
+
+    #[test]
+    fn background_is_rebuilt_only_when_its_key_changes() {
+        let mut app = ChessApp::new();
+        let ctx = egui::Context::default();
+        let key = BackgroundKey {
+            offset: egui::pos2(60.0, 60.0),
+            cell_size: 40.0,
+            theme: Theme::CLASSIC,
+            pixels_per_point: 1.0,
+        };
+        let first = app.background_mesh(&ctx, key);
+        assert!(Arc::ptr_eq(&first, &app.background_mesh(&ctx, key)));
+        app.flipped = true;
+        assert!(Arc::ptr_eq(&first, &app.background_mesh(&ctx, key)));
+
+        let changes = [
+            BackgroundKey {
+                cell_size: 50.0,
+                ..key
+            },
+            BackgroundKey {
+                offset: egui::pos2(80.0, 60.0),
+                ..key
+            },
+            BackgroundKey {
+                theme: Theme::DARK,
+                ..key
+            },
+            BackgroundKey {
+                pixels_per_point: 2.0,
+                ..key
+            },
+        ];
+        for changed in changes {
+            assert!(!Arc::ptr_eq(&first, &app.background_mesh(&ctx, changed)));
+            let rebuilt = app.background_mesh(&ctx, changed);
+            assert!(Arc::ptr_eq(&rebuilt, &app.background_mesh(&ctx, changed)));
+            assert!(!Arc::ptr_eq(&first, &app.background_mesh(&ctx, key)));
+        }
+    }
+
+    #[test]
+    fn cached_background_draws_the_same_as_its_shapes() {
+        let ctx = egui::Context::default();
+        let _ = ctx.run(egui::RawInput::default(), |_| {});
+        for (theme, pixels_per_point) in [(Theme::CLASSIC, 1.0), (Theme::WOOD, 2.0)] {
+            let key = BackgroundKey {
+                offset: egui::pos2(60.0, 60.0),
+                cell_size: 40.0,
+                theme,
+                pixels_per_point,
+            };
+            let clipped = |shape| egui::epaint::ClippedShape {
+                clip_rect: egui::Rect::EVERYTHING,
+                shape,
+            };
+            let meshes = |shapes: Vec<egui::epaint::ClippedShape>| -> Vec<egui::Mesh> {
+                ctx.tessellate(shapes, pixels_per_point)
+                    .into_iter()
+                    .map(|primitive| match primitive.primitive {
+                        egui::epaint::Primitive::Mesh(mesh) => mesh,
+                        egui::epaint::Primitive::Callback(_) => panic!("no callbacks drawn"),
+                    })
+                    .collect()
+            };
+
+            // The same triangles in the same order make the same pixels
+            let direct = meshes(
+                board_background(&key.theme, key.offset, key.cell_size)
+                    .into_iter()
+                    .map(clipped)
+                    .collect(),
+            );
+            let cache = BackgroundCache::new(&ctx, key);
+            let cached = meshes(vec![clipped(egui::Shape::Mesh(cache.mesh.clone()))]);
+            assert_eq!(direct.len(), 1);
+            assert_eq!(cached, direct);
+        }
     }
 }
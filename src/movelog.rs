@@ -0,0 +1,78 @@
+use std::fs::OpenOptions;
+use std::io::{self, Write};
+use std::path::Path;
+
+use crate::game::Pos;
+use crate::notation;
+
+pub const CSV_HEADER: &str = "fen,move,eval_before,eval_after";
+
+/// A played move together with the position it was played from.
+pub struct MoveRecord {
+    pub fen: String,
+    pub from: Pos,
+    pub to: Pos,
+}
+
+/// Formats one CSV row: position FEN, ICCS move, and the evaluations before
+/// and after the move.
+pub fn format_csv_row(record: &MoveRecord, eval_before: i32, eval_after: i32) -> String {
+    format!(
+        "{},{},{},{}",
+        record.fen,
+        notation::move_to_iccs(record.from, record.to),
+        eval_before,
+        eval_after
+    )
+}
+
+/// Appends a row to the CSV file at `path`, writing the header first if the
+/// file is new or empty.
+pub fn append_move_log_csv(
+    path: &Path,
+    record: &MoveRecord,
+    eval_before: i32,
+    eval_after: i32,
+) -> io::Result<()> {
+    let mut file = OpenOptions::new().create(true).append(true).open(path)?;
+    if file.metadata()?.len() == 0 {
+        writeln!(file, "{}", CSV_HEADER)?;
+    }
+    writeln!(file, "{}", format_csv_row(record, eval_before, eval_after))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game::Board;
+
+    #[test]
+    fn two_moves_give_a_header_and_two_rows() {
+        let path = std::env::temp_dir().join(format!("movelog-{}.csv", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+
+        let mut board = Board::new();
+        for (iccs, before, after) in [("h2e2", 0, 20), ("h9g7", 20, -5)] {
+            let (from, to) = notation::iccs_to_move(iccs).unwrap();
+            let record = MoveRecord {
+                fen: board.to_fen(),
+                from,
+                to,
+            };
+            append_move_log_csv(&path, &record, before, after).unwrap();
+            board.try_move(from, to).unwrap();
+        }
+
+        let text = std::fs::read_to_string(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        let lines: Vec<&str> = text.lines().collect();
+        assert_eq!(
+            lines,
+            [
+                CSV_HEADER,
+                "rnbakabnr/9/1c5c1/p1p1p1p1p/9/9/P1P1P1P1P/1C5C1/9/RNBAKABNR w - - 0 1,h2e2,0,20",
+                "rnbakabnr/9/1c5c1/p1p1p1p1p/9/9/P1P1P1P1P/1C2C4/9/RNBAKABNR b - - 1 1,h9g7,20,-5",
+            ]
+        );
+    }
+}
@@ -0,0 +1,182 @@
+//! Network play: two apps exchange moves over TCP, one message per line.
+//!
+//! Moves travel as ICCS text (`h2e2`); the other messages are `resign`,
+//! `draw offer`, `draw accept` and `draw decline`. Nothing received is
+//! trusted: the receiving side checks every move against its own board.
+
+use std::io::{self, Read, Write};
+use std::net::{TcpListener, TcpStream, ToSocketAddrs};
+
+use crate::game::Pos;
+use crate::notation;
+
+/// One line of the protocol.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Message {
+    Move(Pos, Pos),
+    Resign,
+    OfferDraw,
+    AcceptDraw,
+    DeclineDraw,
+}
+
+impl Message {
+    /// The message as sent, without the trailing newline.
+    pub fn to_line(&self) -> String {
+        match self {
+            Message::Move(from, to) => notation::move_to_iccs(*from, *to),
+            Message::Resign => "resign".to_string(),
+            Message::OfferDraw => "draw offer".to_string(),
+            Message::AcceptDraw => "draw accept".to_string(),
+            Message::DeclineDraw => "draw decline".to_string(),
+        }
+    }
+
+    pub fn parse(line: &str) -> Option<Message> {
+        match line.trim() {
+            "resign" => Some(Message::Resign),
+            "draw offer" => Some(Message::OfferDraw),
+            "draw accept" => Some(Message::AcceptDraw),
+            "draw decline" => Some(Message::DeclineDraw),
+            text => notation::iccs_to_move(text).map(|(from, to)| Message::Move(from, to)),
+        }
+    }
+}
+
+/// An established connection to the other player.
+pub struct Connection {
+    stream: TcpStream,
+    /// Bytes received but not yet split into lines.
+    received: Vec<u8>,
+}
+
+/// Waits on `addr` for the other player to join. Blocks until they do.
+pub fn host(addr: impl ToSocketAddrs) -> io::Result<Connection> {
+    let listener = TcpListener::bind(addr)?;
+    let (stream, _) = listener.accept()?;
+    Connection::new(stream)
+}
+
+/// Connects to a player hosting on `addr`.
+pub fn join(addr: impl ToSocketAddrs) -> io::Result<Connection> {
+    Connection::new(TcpStream::connect(addr)?)
+}
+
+impl Connection {
+    fn new(stream: TcpStream) -> io::Result<Self> {
+        stream.set_nodelay(true)?;
+        stream.set_nonblocking(true)?;
+        Ok(Self {
+            stream,
+            received: Vec::new(),
+        })
+    }
+
+    /// Sends `message` in full. The socket is switched to blocking for the
+    /// write, since on a non-blocking one `write_all` gives up with
+    /// `WouldBlock` part way through a line whenever the send buffer is full.
+    pub fn send(&mut self, message: Message) -> io::Result<()> {
+        let line = format!("{}\n", message.to_line());
+        self.stream.set_nonblocking(false)?;
+        let written = self.stream.write_all(line.as_bytes());
+        self.stream.set_nonblocking(true)?;
+        written
+    }
+
+    pub fn send_move(&mut self, from: Pos, to: Pos) -> io::Result<()> {
+        self.send(Message::Move(from, to))
+    }
+
+    /// Returns the next complete message without blocking, or `None` if
+    /// there isn't one yet. Lines that aren't valid messages are skipped.
+    /// Fails once the other side has closed the connection.
+    ///
+    /// Moves and control messages share one ordered stream, so they are
+    /// polled together: a move-only poll would have to hold back a
+    /// resignation or draw offer sent before the next move.
+    pub fn poll_message(&mut self) -> io::Result<Option<Message>> {
+        let mut buf = [0; 512];
+        let mut closed = false;
+        loop {
+            match self.stream.read(&mut buf) {
+                Ok(0) => {
+                    closed = true;
+                    break;
+                }
+                Ok(n) => self.received.extend_from_slice(&buf[..n]),
+                Err(err) if err.kind() == io::ErrorKind::WouldBlock => break,
+                Err(err) if err.kind() == io::ErrorKind::Interrupted => {}
+                Err(err) => return Err(err),
+            }
+        }
+
+        while let Some(end) = self.received.iter().position(|&b| b == b'\n') {
+            let line: Vec<u8> = self.received.drain(..=end).collect();
+            if let Some(message) = Message::parse(&String::from_utf8_lossy(&line)) {
+                return Ok(Some(message));
+            }
+        }
+        if closed {
+            return Err(io::ErrorKind::ConnectionAborted.into());
+        }
+        Ok(None)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::{Duration, Instant};
+
+    #[test]
+    fn messages_round_trip_through_their_lines() {
+        let (from, to) = notation::iccs_to_move("h2e2").unwrap();
+        for message in [
+            Message::Move(from, to),
+            Message::Resign,
+            Message::OfferDraw,
+            Message::AcceptDraw,
+            Message::DeclineDraw,
+        ] {
+            assert_eq!(Message::parse(&message.to_line()), Some(message));
+        }
+        assert_eq!(Message::Move(from, to).to_line(), "h2e2");
+        assert_eq!(Message::parse("h2e2\n"), Some(Message::Move(from, to)));
+        assert_eq!(Message::parse("hello"), None);
+        assert_eq!(Message::parse("z2e2"), None);
+    }
+
+    #[test]
+    fn messages_cross_a_socket_in_order() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let mut sender = join(listener.local_addr().unwrap()).unwrap();
+        let mut receiver = Connection::new(listener.accept().unwrap().0).unwrap();
+
+        let (from, to) = notation::iccs_to_move("b0c2").unwrap();
+        sender.send(Message::OfferDraw).unwrap();
+        sender.stream.write_all(b"not a message\n").unwrap();
+        sender.send_move(from, to).unwrap();
+
+        let mut received = Vec::new();
+        let deadline = Instant::now() + Duration::from_secs(5);
+        while received.len() < 2 && Instant::now() < deadline {
+            match receiver.poll_message().unwrap() {
+                Some(message) => received.push(message),
+                None => std::thread::sleep(Duration::from_millis(5)),
+            }
+        }
+        assert_eq!(received, [Message::OfferDraw, Message::Move(from, to)]);
+
+        drop(sender);
+        let deadline = Instant::now() + Duration::from_secs(5);
+        let closed = loop {
+            match receiver.poll_message() {
+                Ok(None) if Instant::now() < deadline => {
+                    std::thread::sleep(Duration::from_millis(5))
+                }
+                result => break result,
+            }
+        };
+        assert!(closed.is_err());
+    }
+}
@@ -0,0 +1,40 @@
+use crate::game::Pos;
+
+/// ICCS coordinate of `pos`: files `a`-`i` from Red's left, ranks `0`-`9`
+/// counted from Red's side of the board.
+pub fn pos_to_iccs(pos: Pos) -> String {
+    let file = (b'a' + pos.x as u8) as char;
+    let rank = (b'0' + (9 - pos.y) as u8) as char;
+    format!("{}{}", file, rank)
+}
+
+/// ICCS move text, e.g. `h2e2` for Red's opening central cannon.
+pub fn move_to_iccs(from: Pos, to: Pos) -> String {
+    format!("{}{}", pos_to_iccs(from), pos_to_iccs(to))
+}
+
+/// Parses an ICCS coordinate such as `e3`.
+pub fn iccs_to_pos(text: &str) -> Option<Pos> {
+    let bytes = text.as_bytes();
+    if bytes.len() != 2 {
+        return None;
+    }
+    let file = bytes[0].to_ascii_lowercase();
+    let rank = bytes[1];
+    if !(b'a'..=b'i').contains(&file) || !rank.is_ascii_digit() {
+        return None;
+    }
+    Some(Pos::new((file - b'a') as usize, 9 - (rank - b'0') as usize))
+}
+
+/// Parses ICCS move text such as `h2e2` (an optional `-` separator, as in
+/// `H2-E2`, is accepted).
+pub fn iccs_to_move(text: &str) -> Option<(Pos, Pos)> {
+    let text = text.trim();
+    let (from, to) = match text.len() {
+        4 => (text.get(..2)?, text.get(2..)?),
+        5 if text.as_bytes()[2] == b'-' => (text.get(..2)?, text.get(3..)?),
+        _ => return None,
+    };
+    Some((iccs_to_pos(from)?, iccs_to_pos(to)?))
+}
@@ -0,0 +1,115 @@
+use std::collections::HashMap;
+use std::sync::OnceLock;
+
+use crate::game::{Board, Pos};
+use crate::notation;
+
+/// Mainline openings as ICCS move sequences from the start position. Every
+/// prefix of a line is a book position whose reply is the line's next move.
+const BOOK_LINES: &[&[&str]] = &[
+    // Central Cannon vs Screen Horses
+    &["h2e2", "h9g7", "h0g2", "i9h9", "i0h0"],
+    &["h2e2", "h9g7", "h0g2", "b9c7"],
+    &["h2e2", "h9g7", "h0g2", "g6g5"],
+    &["h2e2", "b9c7", "h0g2", "h9g7"],
+    // Same Direction Cannons
+    &["h2e2", "h7e7", "h0g2", "h9g7"],
+    // Opposite Direction Cannons
+    &["h2e2", "b7e7", "h0g2", "b9c7"],
+    &["h2e2", "c6c5"],
+    &["b2e2", "b9c7", "b0c2"],
+    &["b2e2", "h9g7", "b0c2"],
+    // Pawn Opening
+    &["c3c4", "g6g5", "h0g2"],
+    &["c3c4", "b7c7", "h2e2"],
+    // Elephant Opening
+    &["g0e2", "h7f7", "h0g2"],
+    &["g0e2", "c6c5"],
+    &["c0e2", "h7f7"],
+    // Horse Opening
+    &["h0g2", "b9c7", "g3g4"],
+    &["h0g2", "g6g5"],
+];
+
+/// Book replies keyed by the short FEN of the position they are played from.
+/// A malformed or illegal move ends its line there, so a bad entry costs
+/// only the rest of that line; the tests check that there are none.
+fn book() -> &'static HashMap<String, Vec<(Pos, Pos)>> {
+    static BOOK: OnceLock<HashMap<String, Vec<(Pos, Pos)>>> = OnceLock::new();
+    BOOK.get_or_init(|| {
+        let mut book: HashMap<String, Vec<(Pos, Pos)>> = HashMap::new();
+        for line in BOOK_LINES {
+            let mut board = Board::new();
+            for text in line.iter() {
+                let Some((from, to)) = notation::iccs_to_move(text) else {
+                    break;
+                };
+                let fen = board.to_fen_short();
+                if !board.move_piece(from, to) {
+                    break;
+                }
+                let replies = book.entry(fen).or_default();
+                if !replies.contains(&(from, to)) {
+                    replies.push((from, to));
+                }
+            }
+        }
+        book
+    })
+}
+
+/// All book replies registered for `board`'s position.
+pub fn book_moves(board: &Board) -> &'static [(Pos, Pos)] {
+    book()
+        .get(&board.to_fen_short())
+        .map(Vec::as_slice)
+        .unwrap_or(&[])
+}
+
+/// A random book reply for `board`, if the position is in the book.
+pub fn book_move(board: &Board) -> Option<(Pos, Pos)> {
+    let moves = book_moves(board);
+    if moves.is_empty() {
+        None
+    } else {
+        Some(moves[fastrand::usize(..moves.len())])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn every_book_line_is_legal() {
+        for line in BOOK_LINES {
+            let mut board = Board::new();
+            for text in line.iter() {
+                let (from, to) = notation::iccs_to_move(text)
+                    .unwrap_or_else(|| panic!("malformed book move {} in {:?}", text, line));
+                if let Err(err) = board.try_move(from, to) {
+                    panic!("illegal book move {} in {:?}: {}", text, line, err);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn start_position_gets_a_registered_book_move() {
+        let board = Board::new();
+        let options = book_moves(&board);
+        assert!(options.len() >= 4);
+        for _ in 0..20 {
+            let chosen = book_move(&board).unwrap();
+            assert!(options.contains(&chosen));
+        }
+    }
+
+    #[test]
+    fn positions_out_of_book_get_nothing() {
+        let mut board = Board::new();
+        board.try_move(Pos::new(0, 6), Pos::new(0, 5)).unwrap();
+        assert!(book_moves(&board).is_empty());
+        assert_eq!(book_move(&board), None);
+    }
+}
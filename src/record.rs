@@ -0,0 +1,161 @@
+//! Game records: the moves of a whole game as text, for replaying later.
+//! The starting position comes first as a FEN header, then one line per
+//! move number with Red's and Black's moves in ICCS.
+//!
+//! ```text
+//! [FEN "rnbakabnr/9/1c5c1/p1p1p1p1p/9/9/P1P1P1P1P/1C5C1/9/RNBAKABNR w - - 0 1"]
+//! 1. h2e2 h9g7
+//! 2. h0g2
+//! ```
+
+use crate::game::{Board, Color, FenError, MoveError, Pos};
+use crate::notation;
+
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub enum RecordError {
+    Fen(FenError),
+    /// A token that is neither a move number nor an ICCS move.
+    UnknownMove(String),
+    /// The move at this index (counting from 0) is illegal.
+    IllegalMove(usize, MoveError),
+}
+
+impl std::fmt::Display for RecordError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            RecordError::Fen(err) => write!(f, "invalid fen: {}", err),
+            RecordError::UnknownMove(text) => write!(f, "unknown move '{}'", text),
+            RecordError::IllegalMove(index, err) => write!(f, "move {}: {}", index + 1, err),
+        }
+    }
+}
+
+impl std::error::Error for RecordError {}
+
+impl Board {
+    /// Writes the game so far as a record that [`import_game`] reads back.
+    pub fn export_game(&self) -> String {
+        let start = self.positions().swap_remove(0);
+        let mut text = format!("[FEN \"{}\"]\n", start.to_fen());
+        let mut number = start.fullmove_number;
+        let mut turn = start.turn;
+        if turn == Color::Black {
+            text.push_str(&format!("{}. ...", number));
+        }
+        for (from, to) in self.move_log() {
+            match turn {
+                Color::Red => {
+                    text.push_str(&format!("{}. {}", number, notation::move_to_iccs(from, to)))
+                }
+                Color::Black => {
+                    text.push_str(&format!(" {}\n", notation::move_to_iccs(from, to)));
+                    number += 1;
+                }
+            }
+            turn = turn.opposite();
+        }
+        if turn == Color::Black {
+            text.push('\n');
+        }
+        text
+    }
+}
+
+/// Plays `moves` from the standard starting position. Fails on the first
+/// illegal move, giving its index.
+pub fn replay(moves: &[(Pos, Pos)]) -> Result<Board, (usize, MoveError)> {
+    replay_from(Board::new(), moves)
+}
+
+/// Like [`replay`], starting from `start`.
+pub fn replay_from(start: Board, moves: &[(Pos, Pos)]) -> Result<Board, (usize, MoveError)> {
+    let mut board = start;
+    board.apply_moves(moves)?;
+    Ok(board)
+}
+
+/// Reads a record written by [`Board::export_game`] and replays it. Without
+/// a FEN header the game starts from the standard position.
+pub fn import_game(text: &str) -> Result<Board, RecordError> {
+    let mut start = Board::new();
+    let mut moves = Vec::new();
+    for line in text.lines() {
+        if let Some(fen) = line
+            .trim()
+            .strip_prefix("[FEN \"")
+            .and_then(|rest| rest.strip_suffix("\"]"))
+        {
+            start = Board::from_fen(fen).map_err(RecordError::Fen)?;
+            continue;
+        }
+        for token in line.split_whitespace() {
+            // Move numbers ("1.") and the placeholder for Red's missing move
+            if token.ends_with('.') {
+                continue;
+            }
+            let mv = notation::iccs_to_move(token)
+                .ok_or_else(|| RecordError::UnknownMove(token.to_string()))?;
+            moves.push(mv);
+        }
+    }
+    replay_from(start, &moves).map_err(|(index, err)| RecordError::IllegalMove(index, err))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn moves(iccs: &[&str]) -> Vec<(Pos, Pos)> {
+        iccs.iter()
+            .map(|text| notation::iccs_to_move(text).unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn exported_game_replays_to_the_same_position() {
+        let played = moves(&["h2e2", "h9g7", "e2e6", "g7e6", "h0g2"]);
+        let board = replay(&played).unwrap();
+        let text = board.export_game();
+        assert!(
+            text.ends_with("1. h2e2 h9g7\n2. e2e6 g7e6\n3. h0g2\n"),
+            "{}",
+            text
+        );
+
+        let imported = import_game(&text).unwrap();
+        assert_eq!(imported.to_fen(), board.to_fen());
+        assert_eq!(imported.move_log(), played);
+        assert_eq!(
+            replay(&imported.move_log()).unwrap().to_fen(),
+            board.to_fen()
+        );
+    }
+
+    #[test]
+    fn games_starting_with_black_keep_their_start() {
+        let start = Board::from_fen(
+            "rnbakabnr/9/1c5c1/p1p1p1p1p/9/9/P1P1P1P1P/1C2C4/9/RNBAKABNR b - - 1 1",
+        )
+        .unwrap();
+        let board = replay_from(start, &moves(&["h9g7", "h0g2"])).unwrap();
+        let text = board.export_game();
+        assert!(text.contains("1. ... h9g7\n2. h0g2\n"), "{}", text);
+        assert_eq!(import_game(&text).unwrap().to_fen(), board.to_fen());
+    }
+
+    #[test]
+    fn illegal_moves_are_reported_by_index() {
+        assert!(matches!(
+            replay(&moves(&["h2e2", "a0a5"])),
+            Err((1, MoveError::NotYourTurn(_)))
+        ));
+        assert!(matches!(
+            import_game("1. h2e2 h9g7\n2. a0a5"),
+            Err(RecordError::IllegalMove(2, MoveError::BlockedPath { .. }))
+        ));
+        assert_eq!(
+            import_game("1. h2e2 xx").err(),
+            Some(RecordError::UnknownMove("xx".to_string()))
+        );
+    }
+}
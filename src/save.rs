@@ -0,0 +1,307 @@
+//! Plain-text save files. The game is stored as its starting FEN and the
+//! moves played, so undo history survives a reload, followed by the current
+//! FEN as a check and whatever the moves alone cannot rebuild: a result
+//! reached off the board, a pending draw offer and the draw rules in force.
+//! Transient UI state is an optional extra section that pure-logic
+//! consumers skip.
+//!
+//! ```text
+//! start rnbakabnr/9/1c5c1/p1p1p1p1p/9/9/P1P1P1P1P/1C5C1/9/RNBAKABNR w - - 0 1
+//! moves h2e2 h9g7
+//! fen rnbakab1r/9/1c4nc1/p1p1p1p1p/9/9/P1P1P1P1P/1C2C4/9/RNBAKABNR w - - 2 2
+//! result black resignation
+//! draw_offer red
+//! inactivity 120
+//! counting 18 60
+//! ui.selected h2
+//! ui.flipped
+//! ```
+//!
+//! Files with only a `fen` line, as older versions wrote, still load,
+//! without history.
+
+use crate::game::{Board, Color, CountingRules, FenError, GameState, MoveError, Pos, WinReason};
+use crate::notation;
+
+/// Interface state that is restored for convenience but is not part of the
+/// game: the selected square and which way up the board is drawn.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub struct UiState {
+    pub selected: Option<Pos>,
+    /// Black at the bottom instead of Red.
+    pub flipped: bool,
+}
+
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub enum SaveError {
+    MissingFen,
+    Fen(FenError),
+    /// A word on the `moves` line is not an ICCS move.
+    BadMove(String),
+    /// The saved move at this index (from 0) is illegal.
+    Move(usize, MoveError),
+    /// The moves do not lead to the saved `fen`.
+    Mismatch,
+    /// A line that should hold a value does not.
+    BadLine(String),
+}
+
+impl std::fmt::Display for SaveError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SaveError::MissingFen => write!(f, "save file has no fen line"),
+            SaveError::Fen(err) => write!(f, "invalid fen: {}", err),
+            SaveError::BadMove(word) => write!(f, "bad move '{}'", word),
+            SaveError::Move(index, err) => write!(f, "move {}: {}", index + 1, err),
+            SaveError::Mismatch => write!(f, "moves do not lead to the saved position"),
+            SaveError::BadLine(line) => write!(f, "unreadable line '{}'", line),
+        }
+    }
+}
+
+impl std::error::Error for SaveError {}
+
+/// Serializes `board`, plus `ui` when given.
+pub fn save_game(board: &Board, ui: Option<&UiState>) -> String {
+    let mut text = String::new();
+    if !board.history.is_empty() {
+        let start = &board.positions()[0];
+        let moves: Vec<String> = board
+            .move_log()
+            .into_iter()
+            .map(|(from, to)| notation::move_to_iccs(from, to))
+            .collect();
+        text.push_str(&format!("start {}\n", start.to_fen()));
+        text.push_str(&format!("moves {}\n", moves.join(" ")));
+    }
+    text.push_str(&format!("fen {}\n", board.to_fen()));
+    match board.state {
+        GameState::Playing => {}
+        GameState::Won(winner) => {
+            text.push_str(&format!("result {}", color_name(winner)));
+            if let Some(reason) = board.result_reason {
+                text.push_str(&format!(" {}", reason_name(reason)));
+            }
+            text.push('\n');
+        }
+        GameState::Draw => text.push_str("result draw\n"),
+    }
+    if let Some(color) = board.draw_offer {
+        text.push_str(&format!("draw_offer {}\n", color_name(color)));
+    }
+    text.push_str(&format!("inactivity {}\n", board.inactivity_limit));
+    if let Some(counting) = board.counting {
+        text.push_str(&format!(
+            "counting {} {}\n",
+            counting.material_threshold, counting.move_limit
+        ));
+    }
+    if let Some(ui) = ui {
+        if let Some(selected) = ui.selected {
+            text.push_str(&format!(
+                "ui.selected {}\n",
+                notation::pos_to_iccs(selected)
+            ));
+        }
+        if ui.flipped {
+            text.push_str("ui.flipped\n");
+        }
+    }
+    text
+}
+
+/// Restores the game, ignoring any UI section.
+pub fn load_game(text: &str) -> Result<Board, SaveError> {
+    let value = |key: &str| {
+        text.lines()
+            .find_map(|line| line.strip_prefix(key)?.strip_prefix(' '))
+    };
+    let fen = value("fen").ok_or(SaveError::MissingFen)?;
+    let current = Board::from_fen(fen).map_err(SaveError::Fen)?;
+
+    // Rules first, as they decide how the replay ends
+    let mut board = match value("start") {
+        Some(start) => Board::from_fen(start).map_err(SaveError::Fen)?,
+        None => current.clone(),
+    };
+    if let Some(limit) = value("inactivity") {
+        board.inactivity_limit = parse(limit)?;
+    }
+    if let Some(counting) = value("counting") {
+        let mut numbers = counting.split_whitespace();
+        let (Some(threshold), Some(limit), None) = (numbers.next(), numbers.next(), numbers.next())
+        else {
+            return Err(SaveError::BadLine(counting.to_string()));
+        };
+        board.counting = Some(CountingRules {
+            material_threshold: parse(threshold)?,
+            move_limit: parse(limit)?,
+        });
+    }
+
+    if value("start").is_some() {
+        let mut moves = Vec::new();
+        for word in value("moves").unwrap_or("").split_whitespace() {
+            moves.push(notation::iccs_to_move(word).ok_or(SaveError::BadMove(word.to_string()))?);
+        }
+        board
+            .apply_moves(&moves)
+            .map_err(|(index, err)| SaveError::Move(index, err))?;
+        if board.to_fen() != current.to_fen() {
+            return Err(SaveError::Mismatch);
+        }
+    }
+
+    // Results off the board: resignation, agreement, time
+    if let Some(result) = value("result") {
+        let mut words = result.split_whitespace();
+        let bad = || SaveError::BadLine(result.to_string());
+        match words.next() {
+            Some("draw") => {
+                board.state = GameState::Draw;
+                board.result_reason = None;
+            }
+            Some(winner) => {
+                board.state = GameState::Won(parse_color(winner).ok_or_else(bad)?);
+                board.result_reason = match words.next() {
+                    Some(reason) => Some(parse_reason(reason).ok_or_else(bad)?),
+                    None => None,
+                };
+            }
+            None => return Err(bad()),
+        }
+    }
+    if let Some(color) = value("draw_offer") {
+        board.draw_offer = Some(parse_color(color).ok_or(SaveError::BadLine(color.to_string()))?);
+    }
+    Ok(board)
+}
+
+/// Restores the UI section, if the save has one. Unreadable entries are
+/// dropped rather than failing the load.
+pub fn load_ui_state(text: &str) -> Option<UiState> {
+    let mut ui = None;
+    for line in text.lines() {
+        if let Some(value) = line.strip_prefix("ui.selected ") {
+            ui.get_or_insert_with(UiState::default).selected = notation::iccs_to_pos(value);
+        } else if line == "ui.flipped" {
+            ui.get_or_insert_with(UiState::default).flipped = true;
+        }
+    }
+    ui
+}
+
+fn parse(number: &str) -> Result<u32, SaveError> {
+    number
+        .parse()
+        .map_err(|_| SaveError::BadLine(number.to_string()))
+}
+
+fn color_name(color: Color) -> &'static str {
+    match color {
+        Color::Red => "red",
+        Color::Black => "black",
+    }
+}
+
+fn parse_color(name: &str) -> Option<Color> {
+    match name {
+        "red" => Some(Color::Red),
+        "black" => Some(Color::Black),
+        _ => None,
+    }
+}
+
+fn reason_name(reason: WinReason) -> &'static str {
+    match reason {
+        WinReason::Checkmate => "checkmate",
+        WinReason::Stalemate => "stalemate",
+        WinReason::Resignation => "resignation",
+        WinReason::Timeout => "timeout",
+    }
+}
+
+fn parse_reason(name: &str) -> Option<WinReason> {
+    [
+        WinReason::Checkmate,
+        WinReason::Stalemate,
+        WinReason::Resignation,
+        WinReason::Timeout,
+    ]
+    .into_iter()
+    .find(|&reason| reason_name(reason) == name)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn play(board: &mut Board, moves: &[&str]) {
+        for iccs in moves {
+            let (from, to) = notation::iccs_to_move(iccs).unwrap();
+            board.try_move(from, to).unwrap();
+        }
+    }
+
+    #[test]
+    fn round_trip_keeps_history_result_and_rules() {
+        let mut board = Board::new();
+        board.counting = Some(CountingRules::default());
+        board.inactivity_limit = 80;
+        play(&mut board, &["h2e2", "h9g7", "e2e6"]);
+        board.offer_draw(Color::Black);
+        board.resign(Color::Black);
+        let ui = UiState {
+            selected: Some(Pos::new(4, 7)),
+            flipped: true,
+        };
+
+        let text = save_game(&board, Some(&ui));
+        let mut loaded = load_game(&text).unwrap();
+        assert_eq!(loaded.snapshot(), board.snapshot());
+        assert_eq!(loaded.state, GameState::Won(Color::Red));
+        assert_eq!(loaded.result_reason, Some(WinReason::Resignation));
+        assert_eq!(load_ui_state(&text), Some(ui));
+
+        // Undo reaches back to the start
+        assert!(loaded.undo() && loaded.undo() && loaded.undo());
+        assert_eq!(loaded.to_fen(), Board::new().to_fen());
+    }
+
+    #[test]
+    fn round_trip_keeps_draw_offer_and_draws() {
+        let mut board = Board::new();
+        play(&mut board, &["h2e2"]);
+        board.offer_draw(Color::Red);
+        let loaded = load_game(&save_game(&board, None)).unwrap();
+        assert_eq!(loaded.snapshot(), board.snapshot());
+        assert_eq!(load_ui_state(&save_game(&board, None)), None);
+
+        board.accept_draw(Color::Black);
+        let loaded = load_game(&save_game(&board, None)).unwrap();
+        assert_eq!(loaded.state, GameState::Draw);
+        assert_eq!(loaded.snapshot(), board.snapshot());
+    }
+
+    #[test]
+    fn fen_only_saves_still_load() {
+        let text = format!("fen {}\nui.selected h2\n", Board::new().to_fen());
+        let board = load_game(&text).unwrap();
+        assert_eq!(board.to_fen(), Board::new().to_fen());
+        assert!(board.history.is_empty());
+        assert_eq!(
+            load_ui_state(&text).and_then(|ui| ui.selected),
+            notation::iccs_to_pos("h2")
+        );
+    }
+
+    #[test]
+    fn moves_must_reach_the_saved_position() {
+        let mut board = Board::new();
+        play(&mut board, &["h2e2"]);
+        let text = save_game(&board, None).replace("moves h2e2", "moves b2e2");
+        assert_eq!(load_game(&text).err(), Some(SaveError::Mismatch));
+        let text = save_game(&board, None).replace("moves h2e2", "moves a0a5");
+        assert!(matches!(load_game(&text), Err(SaveError::Move(0, _))));
+    }
+}
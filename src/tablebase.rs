@@ -0,0 +1,169 @@
+//! Exact solutions for endgames with very few pieces, by retrograde
+//! analysis: every position reachable from the one given is generated,
+//! then results are worked backwards from the mates.
+//!
+//! Repetition and the inactivity and counting rules are ignored; the result
+//! is what perfect play achieves on the board alone.
+
+use std::collections::{HashMap, VecDeque};
+
+use crate::game::{Board, GameState, WIDTH};
+
+/// Most pieces, Generals included, that [`solve`] takes on. With two
+/// Chariots beside the Generals there are already about a million
+/// positions to go through.
+pub const MAX_PIECES: usize = 4;
+
+/// Result with perfect play, for the side to move.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Outcome {
+    /// Mates in this many plies, counting both sides' moves. Always odd.
+    Win(u32),
+    /// Gets mated in this many plies. Always even; 0 when already mated.
+    Loss(u32),
+    Draw,
+}
+
+/// Solves the position exactly, or returns `None` when it has more than
+/// [`MAX_PIECES`] pieces. A side left without a legal move loses, and a
+/// position where neither side can mate any more is a draw.
+pub fn solve(board: &Board) -> Option<Outcome> {
+    if board.grid.iter().flatten().flatten().count() > MAX_PIECES {
+        return None;
+    }
+    match board.state {
+        GameState::Won(winner) if winner == board.turn => return Some(Outcome::Win(0)),
+        GameState::Won(_) => return Some(Outcome::Loss(0)),
+        GameState::Draw => return Some(Outcome::Draw),
+        GameState::Playing => {}
+    }
+
+    // Forward pass: number every reachable position and link each one to
+    // the positions it can be reached from
+    let mut ids = HashMap::new();
+    let mut nodes = vec![Node::default()];
+    let mut frontier = VecDeque::from([(0, board.position_only())]);
+    ids.insert(key(board), 0);
+    let mut resolved = VecDeque::new();
+    while let Some((id, position)) = frontier.pop_front() {
+        if position.is_insufficient_material() {
+            nodes[id].outcome = Some(Outcome::Draw);
+            continue;
+        }
+        let moves = position.all_legal_moves();
+        if moves.is_empty() {
+            nodes[id].outcome = Some(Outcome::Loss(0));
+            resolved.push_back(id);
+            continue;
+        }
+        nodes[id].unresolved = moves.len() as u32;
+        for (from, to) in moves {
+            let mut next = position.position_only();
+            next.play_unchecked(from, to);
+            let next_id = *ids.entry(key(&next)).or_insert_with(|| {
+                nodes.push(Node::default());
+                frontier.push_back((nodes.len() - 1, next));
+                nodes.len() - 1
+            });
+            nodes[next_id].parents.push(id as u32);
+        }
+    }
+
+    // Backward pass, in order of distance to mate: a position is won once
+    // any move reaches a lost one, and lost once every move reaches a won
+    // one. Whatever is never settled is a draw.
+    while let Some(id) = resolved.pop_front() {
+        let outcome = nodes[id].outcome;
+        for parent_id in std::mem::take(&mut nodes[id].parents) {
+            let parent = &mut nodes[parent_id as usize];
+            if parent.outcome.is_some() {
+                continue;
+            }
+            match outcome {
+                Some(Outcome::Loss(plies)) => parent.outcome = Some(Outcome::Win(plies + 1)),
+                Some(Outcome::Win(plies)) => {
+                    parent.unresolved -= 1;
+                    if parent.unresolved > 0 {
+                        continue;
+                    }
+                    parent.outcome = Some(Outcome::Loss(plies + 1));
+                }
+                _ => continue,
+            }
+            resolved.push_back(parent_id as usize);
+        }
+    }
+    Some(nodes[0].outcome.unwrap_or(Outcome::Draw))
+}
+
+#[derive(Default)]
+struct Node {
+    outcome: Option<Outcome>,
+    /// Moves not yet known to lead to a win for the opponent.
+    unresolved: u32,
+    parents: Vec<u32>,
+}
+
+/// Packs the pieces and side to move into a number, 11 bits per piece in
+/// board scan order and the turn in the lowest bit. Four pieces fit in 45
+/// bits.
+fn key(board: &Board) -> u64 {
+    let mut key = 0;
+    for (y, row) in board.grid.iter().enumerate() {
+        for (x, piece) in row.iter().enumerate() {
+            if let Some(piece) = piece {
+                let square = (y * WIDTH + x) as u64;
+                // Never 0, so boards with fewer pieces can't pack the same
+                let code = (square << 4 | (piece.color as u64) << 3 | piece.piece_type as u64) + 1;
+                key = key << 11 | code;
+            }
+        }
+    }
+    key << 1 | board.turn as u64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game::Color;
+
+    #[test]
+    fn chariot_wins_with_odd_plies_to_mate() {
+        let board = Board::from_fen("4k4/9/9/9/9/9/9/9/9/R2K5 w").unwrap();
+        let Some(Outcome::Win(plies)) = solve(&board) else {
+            panic!("{:?}", solve(&board));
+        };
+        assert_eq!(plies % 2, 1);
+
+        // The best reply leaves Black lost one ply sooner, and nothing
+        // leaves Black better off
+        let mut fastest = u32::MAX;
+        for (from, to) in board.all_legal_moves() {
+            let mut next = board.position_only();
+            next.try_move(from, to).unwrap();
+            match solve(&next) {
+                Some(Outcome::Loss(left)) => {
+                    assert_eq!(left % 2, 0);
+                    fastest = fastest.min(left);
+                }
+                Some(Outcome::Draw) => {}
+                other => panic!("{:?} after a Red move", other),
+            }
+        }
+        assert_eq!(fastest, plies - 1);
+    }
+
+    #[test]
+    fn lone_generals_draw() {
+        let board = Board::from_fen("3k5/9/9/9/9/9/9/9/9/4K4 w").unwrap();
+        assert_eq!(solve(&board), Some(Outcome::Draw));
+    }
+
+    #[test]
+    fn results_off_the_board_are_zero_plies_away() {
+        let mut board = Board::from_fen("4k4/9/9/9/9/9/9/9/9/R2K5 w").unwrap();
+        board.resign(Color::Black);
+        assert_eq!(solve(&board), Some(Outcome::Win(0)));
+        assert_eq!(solve(&Board::new()), None);
+    }
+}
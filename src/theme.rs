@@ -0,0 +1,88 @@
+use eframe::egui::Color32;
+
+/// Colors used to draw the board and pieces.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Theme {
+    pub name: &'static str,
+    /// Grid lines and river text.
+    pub line: Color32,
+    /// Fill behind the whole board, including the labels.
+    pub background: Color32,
+    /// Fill of the river band between the two halves.
+    pub river: Color32,
+    /// File and rank labels.
+    pub label: Color32,
+    pub piece_fill: Color32,
+    pub red_piece: Color32,
+    pub black_piece: Color32,
+    pub selection: Color32,
+    pub cursor: Color32,
+    pub hint: Color32,
+    pub last_move: Color32,
+    /// Halo around a General in check; its alpha is animated.
+    pub check: Color32,
+    /// Thin ring marking pieces pinned to their General.
+    pub pinned: Color32,
+}
+
+impl Theme {
+    /// Plain lines on the window background.
+    pub const CLASSIC: Theme = Theme {
+        name: "Classic",
+        line: Color32::BLACK,
+        background: Color32::TRANSPARENT,
+        river: Color32::TRANSPARENT,
+        label: Color32::DARK_GRAY,
+        piece_fill: Color32::from_rgb(240, 220, 180),
+        red_piece: Color32::RED,
+        black_piece: Color32::BLACK,
+        selection: Color32::from_rgba_premultiplied(0, 255, 0, 100),
+        cursor: Color32::BLUE,
+        hint: Color32::from_rgb(0, 120, 255),
+        last_move: Color32::from_rgba_unmultiplied_const(100, 100, 100, 120),
+        check: Color32::RED,
+        pinned: Color32::from_rgb(200, 120, 0),
+    };
+
+    pub const DARK: Theme = Theme {
+        name: "Dark",
+        line: Color32::from_rgb(170, 170, 170),
+        background: Color32::from_rgb(40, 40, 45),
+        river: Color32::from_rgb(35, 45, 60),
+        label: Color32::from_rgb(140, 140, 140),
+        piece_fill: Color32::from_rgb(60, 60, 65),
+        red_piece: Color32::from_rgb(255, 110, 100),
+        black_piece: Color32::from_rgb(220, 220, 220),
+        selection: Color32::from_rgba_premultiplied(0, 200, 0, 100),
+        cursor: Color32::from_rgb(100, 160, 255),
+        hint: Color32::from_rgb(80, 170, 255),
+        last_move: Color32::from_rgba_unmultiplied_const(200, 200, 200, 120),
+        check: Color32::from_rgb(255, 80, 70),
+        pinned: Color32::from_rgb(255, 190, 60),
+    };
+
+    pub const WOOD: Theme = Theme {
+        name: "Wood",
+        line: Color32::from_rgb(70, 40, 20),
+        background: Color32::from_rgb(205, 160, 100),
+        river: Color32::from_rgb(185, 140, 85),
+        label: Color32::from_rgb(90, 55, 30),
+        piece_fill: Color32::from_rgb(245, 225, 180),
+        red_piece: Color32::from_rgb(180, 20, 20),
+        black_piece: Color32::from_rgb(30, 30, 30),
+        selection: Color32::from_rgba_premultiplied(0, 255, 0, 100),
+        cursor: Color32::BLUE,
+        hint: Color32::from_rgb(0, 90, 220),
+        last_move: Color32::from_rgba_unmultiplied_const(100, 100, 100, 120),
+        check: Color32::from_rgb(200, 0, 0),
+        pinned: Color32::from_rgb(150, 60, 150),
+    };
+
+    pub const PRESETS: [Theme; 3] = [Theme::CLASSIC, Theme::DARK, Theme::WOOD];
+}
+
+impl Default for Theme {
+    fn default() -> Self {
+        Theme::CLASSIC
+    }
+}
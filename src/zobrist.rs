@@ -0,0 +1,89 @@
+//! Zobrist hashing: a 64-bit key per position, for the search's
+//! transposition table.
+
+use std::sync::OnceLock;
+
+use crate::game::{Board, Color};
+
+struct Keys {
+    /// Indexed by color, `PieceType as usize`, then `y * 9 + x`.
+    pieces: [[[u64; 90]; 7]; 2],
+    black_to_move: u64,
+}
+
+fn keys() -> &'static Keys {
+    static KEYS: OnceLock<Keys> = OnceLock::new();
+    KEYS.get_or_init(|| {
+        // Fixed seed so hashes are the same from run to run
+        let mut rng = fastrand::Rng::with_seed(0x5851_f42d_4c95_7f2d);
+        let mut pieces = [[[0; 90]; 7]; 2];
+        for key in pieces.iter_mut().flatten().flatten() {
+            *key = rng.u64(..);
+        }
+        Keys {
+            pieces,
+            black_to_move: rng.u64(..),
+        }
+    })
+}
+
+impl Board {
+    /// Hash of the piece placement and side to move. Equal positions hash
+    /// equal regardless of how they were reached.
+    pub fn zobrist_hash(&self) -> u64 {
+        let keys = keys();
+        let mut hash = 0;
+        for (y, row) in self.grid.iter().enumerate() {
+            for (x, piece) in row.iter().enumerate() {
+                if let Some(piece) = piece {
+                    hash ^= keys.pieces[piece.color as usize][piece.piece_type as usize][y * 9 + x];
+                }
+            }
+        }
+        if self.turn == Color::Black {
+            hash ^= keys.black_to_move;
+        }
+        hash
+    }
+
+    /// Hash shared by a position and its mirror image across the centre
+    /// file (see [`Board::mirror_horizontal`]): the smaller of the two
+    /// positions' hashes, computed in one pass without building the mirror.
+    pub fn canonical_hash(&self) -> u64 {
+        let keys = keys();
+        let (mut hash, mut mirrored) = (0, 0);
+        for (y, row) in self.grid.iter().enumerate() {
+            for (x, piece) in row.iter().enumerate() {
+                if let Some(piece) = piece {
+                    let squares = &keys.pieces[piece.color as usize][piece.piece_type as usize];
+                    hash ^= squares[y * 9 + x];
+                    mirrored ^= squares[y * 9 + 8 - x];
+                }
+            }
+        }
+        if self.turn == Color::Black {
+            hash ^= keys.black_to_move;
+            mirrored ^= keys.black_to_move;
+        }
+        hash.min(mirrored)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::game::Board;
+
+    #[test]
+    fn mirror_images_share_only_the_canonical_hash() {
+        let board =
+            Board::from_fen("r1bakabnr/9/1cn4c1/p1p1p1p1p/9/9/P1P1P1P1P/1C2C1N2/9/RNBAKAB1R w")
+                .unwrap();
+        let mirror = board.mirror_horizontal();
+        assert_ne!(mirror.zobrist_hash(), board.zobrist_hash());
+        assert_eq!(mirror.canonical_hash(), board.canonical_hash());
+
+        let mut black = board.clone();
+        black.turn = black.turn.opposite();
+        assert_ne!(black.canonical_hash(), board.canonical_hash());
+    }
+}
@@ -0,0 +1,98 @@
+//! Drives the `protocol` binary over its stdin and stdout.
+
+use std::io::{BufRead, BufReader, Write};
+use std::process::{Command, Stdio};
+
+use chinese_chess::game::{Board, Pos};
+use chinese_chess::notation;
+
+/// Sends `commands` to a fresh engine, one per line, and returns what it
+/// printed.
+fn run(commands: &[&str]) -> Vec<String> {
+    let mut engine = Command::new(env!("CARGO_BIN_EXE_protocol"))
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()
+        .expect("the protocol binary starts");
+    let mut stdin = engine.stdin.take().unwrap();
+    for command in commands {
+        writeln!(stdin, "{}", command).unwrap();
+    }
+    writeln!(stdin, "quit").unwrap();
+    drop(stdin);
+    let lines = BufReader::new(engine.stdout.take().unwrap())
+        .lines()
+        .map(Result::unwrap)
+        .collect();
+    assert!(engine.wait().unwrap().success());
+    lines
+}
+
+fn best_move(line: &str) -> (Pos, Pos) {
+    let iccs = line.strip_prefix("bestmove ").expect("a bestmove line");
+    notation::iccs_to_move(iccs).expect("an ICCS move")
+}
+
+#[test]
+fn go_answers_with_a_legal_move() {
+    let output = run(&["isready", "position startpos", "go depth 2"]);
+    assert_eq!(output[0], "readyok");
+    assert_eq!(output.len(), 2, "{:?}", output);
+    assert!(
+        Board::new()
+            .all_legal_moves()
+            .contains(&best_move(&output[1]))
+    );
+
+    let output = run(&["position startpos moves h2e2 h9g7", "go depth 2"]);
+    let mut board = Board::new();
+    for iccs in ["h2e2", "h9g7"] {
+        let (from, to) = notation::iccs_to_move(iccs).unwrap();
+        board.try_move(from, to).unwrap();
+    }
+    assert!(board.all_legal_moves().contains(&best_move(&output[0])));
+}
+
+#[test]
+fn refused_position_is_not_searched() {
+    let output = run(&[
+        "position startpos moves h2e2",
+        "position startpos moves a0a5",
+        "go depth 1",
+        "position startpos",
+        "go depth 1",
+    ]);
+    assert_eq!(output.len(), 3, "{:?}", output);
+    assert!(
+        output[0].starts_with("info string move 1 'a0a5'"),
+        "{:?}",
+        output
+    );
+    assert!(
+        output[1].starts_with("info string no position"),
+        "{:?}",
+        output
+    );
+    assert!(
+        Board::new()
+            .all_legal_moves()
+            .contains(&best_move(&output[2]))
+    );
+}
+
+#[test]
+fn depth_beyond_the_maximum_is_refused() {
+    // 2^32 + 1 would wrap round to a 1-ply search if it were truncated
+    let output = run(&["position startpos", "go depth 4294967297", "go depth 1"]);
+    assert_eq!(output.len(), 2, "{:?}", output);
+    assert!(
+        output[0].starts_with("info string depth 4294967297 is deeper"),
+        "{:?}",
+        output
+    );
+    assert!(
+        Board::new()
+            .all_legal_moves()
+            .contains(&best_move(&output[1]))
+    );
+}